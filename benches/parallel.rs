@@ -0,0 +1,67 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use json2csv_processor::config::{Config, Parameters};
+use json2csv_processor::parser::Parser;
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+const FILES: usize = 200;
+const RECORDS_PER_FILE: usize = 50;
+
+/// Many small files of orders with nested objects and item arrays, the typical
+/// output of an extractor paging through an API.
+fn write_input(dir: &TempDir) -> Vec<PathBuf> {
+    (0..FILES)
+        .map(|file| {
+            let records: Vec<_> = (0..RECORDS_PER_FILE)
+                .map(|i| {
+                    json!({
+                        "id": file * RECORDS_PER_FILE + i,
+                        "created": "2024-01-31T12:00:00Z",
+                        "customer": {"name": format!("Customer {}", i), "vip": i % 7 == 0},
+                        "items": (0..5)
+                            .map(|n| json!({"sku": format!("SKU-{}", n), "price": n as f64 * 1.25}))
+                            .collect::<Vec<_>>(),
+                        "tags": ["new", "web"],
+                    })
+                })
+                .collect();
+            let path = dir.path().join(format!("page{:04}.json", file));
+            fs::write(&path, serde_json::to_string(&records).unwrap()).unwrap();
+            path
+        })
+        .collect()
+}
+
+fn process_files(c: &mut Criterion) {
+    let input = TempDir::new().unwrap();
+    let output = TempDir::new().unwrap();
+    let paths = write_input(&input);
+
+    let mut group = c.benchmark_group("process_files");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(FILES as u64));
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let config = Config {
+                        parameters: Parameters::default(),
+                    };
+                    let mut parser = Parser::new(config, output.path().to_path_buf());
+                    parser
+                        .process_files(&paths, threads, |_, result| result)
+                        .unwrap();
+                    parser
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, process_files);
+criterion_main!(benches);
@@ -1,35 +1,181 @@
+use crate::parser::ROOT_TABLE;
+use crate::selector::Selector;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
     #[error("Invalid input type: {0}")]
     InvalidInputType(String),
-    #[error("Invalid configuration: {0}")]
-    InvalidConfig(String),
+    #[error("Invalid configuration at {path}: {reason}")]
+    InvalidConfig {
+        /// JSON path of the offending node, e.g. `parameters.mapping.items.destination`.
+        path: String,
+        reason: InvalidConfigReason,
+    },
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InvalidConfigReason {
+    #[error("destination table '{0}' is already used")]
+    DuplicateTable(String),
+    #[error("destination column '{0}' is already used in this table")]
+    DuplicateColumn(String),
+    #[error("destination must not be empty")]
+    EmptyDestination,
+    #[error("'{0}' is not a valid Storage table name, use only alphanumerics, '_' and '-'")]
+    InvalidTableName(String),
+    #[error("'{0}' is not a valid Storage column name, use only alphanumerics and '_'")]
+    InvalidColumnName(String),
+    #[error("primary_key is only allowed on column mappings")]
+    PrimaryKeyOnTable,
+    #[error("'{0}' is not a valid root node path")]
+    InvalidRootNode(String),
+    #[error("root_node and mapping cannot be combined with root_nodes")]
+    ConflictingRootNodes,
+    #[error("'{0}' cannot be used, only ASCII characters other than line breaks are allowed")]
+    InvalidCsvCharacter(char),
+    #[error("delimiter and quote must differ")]
+    DelimiterEqualsQuote,
+    #[error("compression level {0} is out of range, use 0 to 9")]
+    InvalidCompressionLevel(u32),
+    #[error("slice size must be greater than 0")]
+    EmptySlice,
+    #[error("'{0}' is not a valid glob pattern")]
+    InvalidGlob(String),
+    #[error("column must not be empty")]
+    EmptyColumn,
+    #[error("'{0}' cannot hold the generated key of the child rows as it is not a valid Storage column name, map a primary_key column instead")]
+    InvalidKeyColumn(String),
+    #[error("column '{0}' is needed for the generated key of the child rows, map a primary_key column or use another destination")]
+    KeyColumnConflict(String),
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum InputType {
+    #[default]
     Files,
     Tables,
 }
 
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    /// Decided by the file extension, `.jsonl` and `.ndjson` files are read as NDJSON.
+    #[default]
+    Auto,
+    Json,
+    /// Newline-delimited JSON, one root record per line.
+    Ndjson,
+}
+
+/// CSV tables holding one JSON document per row, e.g. Storage tables read with
+/// `in_type` `tables`. Their `.csv` files, possibly compressed, are converted along
+/// with the JSON files.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CsvInput {
+    /// Column parsed as JSON, `root_node` is applied to each value. Rows where it is
+    /// empty are skipped.
+    pub json_column: String,
+    /// Columns copied into the rows of the root table, e.g. the primary key of the
+    /// input table to join the converted records back to their row.
+    #[serde(default)]
+    pub carry_columns: Vec<String>,
+}
+
+/// Selection of the input files by glob patterns matched against their path relative
+/// to the input folder. Patterns without a `/` match the file name in any folder.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct InputFiles {
+    /// Only files matching one of these are converted, all input files when empty.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Levels of folders searched, `1` for the files directly in the input folder.
+    pub max_depth: Option<usize>,
+    pub order: FileOrder,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileOrder {
+    /// The order the files are listed by the file system.
+    #[default]
+    None,
+    /// By the path relative to the input folder.
+    Name,
+    /// Oldest modification time first, by name for the same time.
+    Mtime,
+}
+
+/// What happens when an input file cannot be converted.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorPolicy {
+    /// Abort the whole job.
+    #[default]
+    Fail,
+    /// Leave the file out and continue.
+    Skip,
+    /// Copy the file to `out/files/failed/` with an error report and continue.
+    Quarantine,
+}
+
+/// What happens when the table schemas differ from the previous run's.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaDriftPolicy {
+    /// Do not compare the schemas.
+    Ignore,
+    /// Print the changes and continue.
+    #[default]
+    Warn,
+    /// Print the changes and abort before writing any table.
+    Fail,
+}
+
+/// How inferred columns are written when a key holds a scalar in some records
+/// and an object in others.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeConflictPolicy {
+    /// Scalars keep the `<key>` column, objects are flattened into `<key>_<child>` columns.
+    #[default]
+    Split,
+    /// Objects are written as a JSON string into the `<key>` column.
+    Stringify,
+    /// Fail the file.
+    Error,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ColumnMapping {
     pub destination: String,
-    #[serde(default)]
+    /// Also accepts the Generic Extractor spelling `primaryKey`.
+    #[serde(default, alias = "primaryKey")]
     pub primary_key: bool,
+    /// Joins an array of scalars into a single cell instead of writing it as JSON.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TableMapping {
     pub destination: String,
+    #[serde(alias = "parentKey")]
     pub parent_key: Option<ColumnMapping>,
+    /// Not supported on tables, parsed only so that `Config::validate` can reject it.
+    #[serde(
+        default,
+        alias = "primaryKey",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub primary_key: bool,
     #[serde(rename = "tableMapping")]
-    pub table_mapping: HashMap<String, MappingType>,
+    pub table_mapping: IndexMap<String, MappingType>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -37,31 +183,515 @@ pub struct TableMapping {
 pub enum MappingType {
     #[serde(rename = "column")]
     Column { mapping: ColumnMapping },
+    /// Writes the matched value, typically an object or an array, as compact JSON
+    /// into a single column.
+    #[serde(rename = "json")]
+    Json { mapping: ColumnMapping },
     #[serde(rename = "table")]
     Table(TableMapping),
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// One of several root nodes converted from the same file, each into its own table family.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RootNode {
+    /// Selector of the root records, with the same syntax as `Parameters::root_node`.
+    #[serde(default)]
+    pub root_node: String,
+    /// Output table of the root records.
+    pub destination: String,
+    #[serde(default)]
+    pub mapping: IndexMap<String, MappingType>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     pub parameters: Parameters,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct Parameters {
     #[serde(default)]
-    pub mapping: HashMap<String, MappingType>,
+    pub mapping: IndexMap<String, MappingType>,
     #[serde(default)]
     pub incremental: bool,
     #[serde(default)]
     pub root_node: String,
+    /// Replaces `root_node` and `mapping` when several table families are read from one file.
+    #[serde(default)]
+    pub root_nodes: Vec<RootNode>,
     pub in_type: InputType,
+    /// Which files of the input folder are converted, and in which order.
+    #[serde(default)]
+    pub input_files: InputFiles,
+    #[serde(default)]
+    pub input_format: InputFormat,
+    /// Reads JSON documents stored in a column of CSV input tables.
+    #[serde(default)]
+    pub csv_input: Option<CsvInput>,
     #[serde(default)]
     pub add_file_name: bool,
+    /// Parent object field used as the child tables' parent key instead of a generated hash.
+    #[serde(default)]
+    pub parent_key_field: Option<String>,
+    #[serde(default)]
+    pub flatten: FlattenOptions,
+    /// Parse the input incrementally and write rows as they are produced, keeping memory bounded.
+    #[serde(default)]
+    pub streaming: bool,
+    /// Worker threads converting input files in parallel, `0` for one per CPU core.
+    /// Files are converted one by one when unset.
+    #[serde(default)]
+    pub threads: Option<usize>,
+    #[serde(default)]
+    pub on_error: ErrorPolicy,
+    #[serde(default)]
+    pub on_type_conflict: TypeConflictPolicy,
+    #[serde(default)]
+    pub schema_drift: SchemaDriftPolicy,
+    #[serde(default)]
+    pub csv: CsvOptions,
+    #[serde(default)]
+    pub format: FormatOptions,
+    #[serde(default)]
+    pub compression: CompressionOptions,
+    #[serde(default)]
+    pub slicing: SlicingOptions,
+}
+
+/// How nested objects are flattened into prefixed columns.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct FlattenOptions {
+    /// Joins the parent key and the nested key, e.g. `price_currency`.
+    pub separator: String,
+    /// Nesting depth past which objects are written as a JSON string, unlimited when unset.
+    pub max_depth: Option<usize>,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        Self {
+            separator: "_".to_string(),
+            max_depth: None,
+        }
+    }
+}
+
+/// How JSON values are rendered in CSV cells. Numbers are always written with the
+/// digits they have in the input.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct FormatOptions {
+    pub boolean: BooleanFormat,
+    /// Written for JSON `null`, keys missing from an object stay empty.
+    pub null: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BooleanFormat {
+    /// `true` and `false`
+    #[default]
+    Text,
+    /// `1` and `0`
+    Numeric,
+}
+
+/// Dialect of the output CSV files.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub quote: char,
+    pub quote_style: QuoteStyle,
+    pub line_terminator: LineTerminator,
+    /// Quote the header like the rows, otherwise column names are only quoted when needed.
+    pub quote_header: bool,
+    /// Start the files with a UTF-8 byte order mark.
+    pub bom: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            quote_style: QuoteStyle::default(),
+            line_terminator: LineTerminator::default(),
+            quote_header: false,
+            bom: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteStyle {
+    #[default]
+    Always,
+    /// Only values containing the delimiter, the quote or a line break.
+    Necessary,
+    /// Values that are not numbers.
+    NonNumeric,
+    Never,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineTerminator {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+/// Compression of the output tables.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct CompressionOptions {
+    pub format: CompressionFormat,
+    /// From 0, fastest, to 9, smallest.
+    pub level: u32,
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self {
+            format: CompressionFormat::default(),
+            level: 6,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionFormat {
+    #[default]
+    None,
+    /// `<table>.csv.gz`, described by `<table>.csv.gz.manifest`.
+    Gzip,
+}
+
+/// Splits the output tables into slices without a header, `<table>.csv/part0001.csv`,
+/// `<table>.csv/part0002.csv`, ... Tables are written as single files when neither
+/// limit is set.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct SlicingOptions {
+    /// Rows per slice.
+    pub max_rows: Option<usize>,
+    /// Uncompressed size in bytes after which the next row starts a new slice.
+    pub max_bytes: Option<u64>,
+}
+
+impl Parameters {
+    /// Whether some table is inferred from the input, i.e. is reached without a
+    /// mapping of its own: the root table of an empty mapping, or a `table` with
+    /// an empty `tableMapping`.
+    pub fn infers_tables(&self) -> bool {
+        if self.root_nodes.is_empty() {
+            infers_tables(&self.mapping)
+        } else {
+            self.root_nodes
+                .iter()
+                .any(|root_node| infers_tables(&root_node.mapping))
+        }
+    }
+}
+
+fn infers_tables(mapping: &IndexMap<String, MappingType>) -> bool {
+    mapping.is_empty()
+        || mapping.values().any(|entry| match entry {
+            MappingType::Table(table) => infers_tables(&table.table_mapping),
+            MappingType::Column { .. } | MappingType::Json { .. } => false,
+        })
+}
+
+impl SlicingOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.max_rows.is_some() || self.max_bytes.is_some()
+    }
+}
+
+impl CompressionFormat {
+    /// Appended to the `.csv` extension of the table files.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Gzip => ".gz",
+        }
+    }
 }
 
 impl Config {
     pub fn validate(&self) -> Result<(), ConfigError> {
-        // Add validation logic here if needed
+        let params = &self.parameters;
+        validate_csv(&params.csv)?;
+        if params.compression.level > 9 {
+            return Err(invalid(
+                "parameters.compression.level",
+                InvalidConfigReason::InvalidCompressionLevel(params.compression.level),
+            ));
+        }
+        validate_globs(
+            &params.input_files.include,
+            "parameters.input_files.include",
+        )?;
+        validate_globs(
+            &params.input_files.exclude,
+            "parameters.input_files.exclude",
+        )?;
+        if let Some(csv_input) = &params.csv_input {
+            validate_csv_input(csv_input)?;
+        }
+        if params.slicing.max_rows == Some(0) {
+            return Err(invalid(
+                "parameters.slicing.max_rows",
+                InvalidConfigReason::EmptySlice,
+            ));
+        }
+        if params.slicing.max_bytes == Some(0) {
+            return Err(invalid(
+                "parameters.slicing.max_bytes",
+                InvalidConfigReason::EmptySlice,
+            ));
+        }
+
+        if params.root_nodes.is_empty() {
+            validate_root_node(&params.root_node, "parameters.root_node")?;
+
+            let mut validator = MappingValidator {
+                tables: HashSet::from([ROOT_TABLE.to_string()]),
+                separator: &params.flatten.separator,
+            };
+            return validator.validate(&params.mapping, "parameters.mapping", None);
+        }
+
+        if !params.root_node.is_empty() || !params.mapping.is_empty() {
+            return Err(invalid(
+                "parameters.root_nodes",
+                InvalidConfigReason::ConflictingRootNodes,
+            ));
+        }
+
+        let mut validator = MappingValidator {
+            tables: HashSet::new(),
+            separator: &params.flatten.separator,
+        };
+        for (i, root_node) in params.root_nodes.iter().enumerate() {
+            let path = format!("parameters.root_nodes[{}]", i);
+            validate_root_node(&root_node.root_node, &format!("{}.root_node", path))?;
+
+            let destination_path = format!("{}.destination", path);
+            validate_name(&root_node.destination, &destination_path, true)?;
+            if !validator.tables.insert(root_node.destination.clone()) {
+                return Err(invalid(
+                    &destination_path,
+                    InvalidConfigReason::DuplicateTable(root_node.destination.clone()),
+                ));
+            }
+            validator.validate(&root_node.mapping, &format!("{}.mapping", path), None)?;
+        }
         Ok(())
     }
 }
+
+fn invalid(path: &str, reason: InvalidConfigReason) -> ConfigError {
+    ConfigError::InvalidConfig {
+        path: path.to_string(),
+        reason,
+    }
+}
+
+/// Appends a mapping key to a JSON path, quoting keys that contain dots.
+fn child_path(path: &str, key: &str) -> String {
+    if key.contains('.') {
+        format!("{}[\"{}\"]", path, key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn validate_root_node(root_node: &str, path: &str) -> Result<(), ConfigError> {
+    if root_node.parse::<Selector>().is_err() {
+        return Err(invalid(
+            path,
+            InvalidConfigReason::InvalidRootNode(root_node.to_string()),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_csv(csv: &CsvOptions) -> Result<(), ConfigError> {
+    for (c, path) in [
+        (csv.delimiter, "parameters.csv.delimiter"),
+        (csv.quote, "parameters.csv.quote"),
+    ] {
+        if !c.is_ascii() || c == '\n' || c == '\r' {
+            return Err(invalid(path, InvalidConfigReason::InvalidCsvCharacter(c)));
+        }
+    }
+    if csv.delimiter == csv.quote {
+        return Err(invalid(
+            "parameters.csv.quote",
+            InvalidConfigReason::DelimiterEqualsQuote,
+        ));
+    }
+    Ok(())
+}
+
+fn validate_globs(patterns: &[String], path: &str) -> Result<(), ConfigError> {
+    for (i, pattern) in patterns.iter().enumerate() {
+        if globset::Glob::new(pattern).is_err() {
+            return Err(invalid(
+                &format!("{}[{}]", path, i),
+                InvalidConfigReason::InvalidGlob(pattern.clone()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+struct MappingValidator<'a> {
+    /// Destination tables seen so far across the whole mapping tree.
+    tables: HashSet<String>,
+    /// Joins the parts of nested keys in the names of generated key columns.
+    separator: &'a str,
+}
+
+impl MappingValidator<'_> {
+    fn validate(
+        &mut self,
+        mapping: &IndexMap<String, MappingType>,
+        path: &str,
+        parent_key: Option<(&ColumnMapping, String)>,
+    ) -> Result<(), ConfigError> {
+        let mut columns: Vec<(&ColumnMapping, String)> = mapping
+            .iter()
+            .filter_map(|(key, mapping_type)| match mapping_type {
+                MappingType::Column { mapping: column } | MappingType::Json { mapping: column } => {
+                    Some((
+                        column,
+                        format!("{}.mapping.destination", child_path(path, key)),
+                    ))
+                }
+                MappingType::Table(_) => None,
+            })
+            .collect();
+        let has_primary_key = columns.iter().any(|(column, _)| column.primary_key);
+        columns.extend(parent_key);
+
+        let mut seen = HashSet::new();
+        for (column, column_path) in &columns {
+            validate_name(&column.destination, column_path, false)?;
+            if !seen.insert(column.destination.clone()) {
+                return Err(invalid(
+                    column_path,
+                    InvalidConfigReason::DuplicateColumn(column.destination.clone()),
+                ));
+            }
+        }
+
+        // Without a primary key the rows keep the generated keys of their child rows
+        // in columns named after the keys of the child tables
+        if !has_primary_key {
+            for (key, mapping_type) in mapping {
+                if !matches!(mapping_type, MappingType::Table(_)) {
+                    continue;
+                }
+                let column = key.replace('.', self.separator);
+                let table_path = child_path(path, key);
+                let valid = !column.is_empty()
+                    && column
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid {
+                    return Err(invalid(
+                        &table_path,
+                        InvalidConfigReason::InvalidKeyColumn(column),
+                    ));
+                }
+                if !seen.insert(column.clone()) {
+                    return Err(invalid(
+                        &table_path,
+                        InvalidConfigReason::KeyColumnConflict(column),
+                    ));
+                }
+            }
+        }
+
+        for (key, mapping_type) in mapping {
+            let MappingType::Table(table) = mapping_type else {
+                continue;
+            };
+            let table_path = child_path(path, key);
+            if table.primary_key {
+                return Err(invalid(
+                    &format!("{}.primary_key", table_path),
+                    InvalidConfigReason::PrimaryKeyOnTable,
+                ));
+            }
+
+            let destination_path = format!("{}.destination", table_path);
+            validate_name(&table.destination, &destination_path, true)?;
+            if !self.tables.insert(table.destination.clone()) {
+                return Err(invalid(
+                    &destination_path,
+                    InvalidConfigReason::DuplicateTable(table.destination.clone()),
+                ));
+            }
+
+            let parent_key = table
+                .parent_key
+                .as_ref()
+                .map(|column| (column, format!("{}.parent_key.destination", table_path)));
+            self.validate(
+                &table.table_mapping,
+                &format!("{}.tableMapping", table_path),
+                parent_key,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn validate_csv_input(csv_input: &CsvInput) -> Result<(), ConfigError> {
+    if csv_input.json_column.is_empty() {
+        return Err(invalid(
+            "parameters.csv_input.json_column",
+            InvalidConfigReason::EmptyColumn,
+        ));
+    }
+    let mut seen = HashSet::new();
+    for (i, column) in csv_input.carry_columns.iter().enumerate() {
+        let path = format!("parameters.csv_input.carry_columns[{}]", i);
+        validate_name(column, &path, false)?;
+        if !seen.insert(column.as_str()) {
+            return Err(invalid(
+                &path,
+                InvalidConfigReason::DuplicateColumn(column.clone()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn validate_name(name: &str, path: &str, is_table: bool) -> Result<(), ConfigError> {
+    if name.is_empty() {
+        return Err(invalid(path, InvalidConfigReason::EmptyDestination));
+    }
+
+    let valid = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || (is_table && c == '-'));
+    match (valid, is_table) {
+        (true, _) => Ok(()),
+        (false, true) => Err(invalid(
+            path,
+            InvalidConfigReason::InvalidTableName(name.to_string()),
+        )),
+        (false, false) => Err(invalid(
+            path,
+            InvalidConfigReason::InvalidColumnName(name.to_string()),
+        )),
+    }
+}
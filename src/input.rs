@@ -0,0 +1,208 @@
+use crate::config::{FileOrder, Parameters};
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs::{self, File};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Extensions of compressed files, stripped before the format of the content is
+/// decided by the remaining extension, e.g. `orders.jsonl.gz`.
+const COMPRESSION_EXTENSIONS: [&str; 5] = ["gz", "zst", "zstd", "bz2", "xz"];
+
+/// Compression of an input stream, recognized by its magic number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    const MAGIC_LEN: usize = 6;
+
+    fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            [b'B', b'Z', b'h', ..] => Some(Self::Bzip2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00] => Some(Self::Xz),
+            _ => None,
+        }
+    }
+}
+
+/// Archives whose JSON entries are converted one after another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Archive {
+    Zip,
+    /// Plain or compressed, e.g. `.tar.gz` or `.tgz`.
+    Tar,
+}
+
+impl Archive {
+    fn detect(name: &str) -> Option<Self> {
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if strip_compression(name).ends_with(".tar") || name.ends_with(".tgz") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// File name without its compression extension, `orders.json.gz` becomes `orders.json`.
+pub fn strip_compression(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if COMPRESSION_EXTENSIONS.contains(&extension) => stem,
+        _ => name,
+    }
+}
+
+/// Whether the file holds JSON, possibly compressed, or is an archive of such files.
+pub fn is_input(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    is_json(name) || is_archive(path)
+}
+
+/// Whether the file is a zip or tar archive, judged by its name.
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    Archive::detect(name).is_some()
+}
+
+/// `.json`, `.jsonl` or `.ndjson`, possibly followed by a compression extension.
+fn is_json(name: &str) -> bool {
+    Path::new(strip_compression(name))
+        .extension()
+        .is_some_and(|ext| ext == "json" || ext == "jsonl" || ext == "ndjson")
+}
+
+/// `.csv`, possibly followed by a compression extension.
+pub fn is_csv(name: &str) -> bool {
+    Path::new(strip_compression(name))
+        .extension()
+        .is_some_and(|ext| ext == "csv")
+}
+
+/// Input files found under `dir` and selected by `input_files`, in its order. CSV
+/// tables are only input files when `csv_input` is configured.
+pub fn list_files(dir: &Path, params: &Parameters) -> Result<Vec<PathBuf>> {
+    let input_files = &params.input_files;
+    let include = glob_set(&input_files.include)?;
+    let exclude = glob_set(&input_files.exclude)?;
+
+    let mut walker = WalkDir::new(dir);
+    if let Some(max_depth) = input_files.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let mut files = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+        let path = entry.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let csv = params.csv_input.is_some() && is_csv(name);
+        if !path.is_file() || !(is_input(path) || csv) {
+            continue;
+        }
+        let relative_path = path.strip_prefix(dir).unwrap_or(path);
+        let included = input_files.include.is_empty() || include.is_match(relative_path);
+        if included && !exclude.is_match(relative_path) {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    match input_files.order {
+        FileOrder::None => {}
+        FileOrder::Name => files.sort(),
+        FileOrder::Mtime => {
+            let mut modified = files
+                .into_iter()
+                .map(|path| Ok((fs::metadata(&path)?.modified()?, path)))
+                .collect::<Result<Vec<_>>>()?;
+            modified.sort();
+            files = modified.into_iter().map(|(_, path)| path).collect();
+        }
+    }
+    Ok(files)
+}
+
+/// Compiles glob patterns where `*` does not cross folders. Patterns without a `/`
+/// match the file name in any folder, like in `.gitignore`.
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = if pattern.contains('/') {
+            pattern.clone()
+        } else {
+            format!("**/{}", pattern)
+        };
+        builder.add(GlobBuilder::new(&pattern).literal_separator(true).build()?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Opens a file, decompressing it when its content is compressed.
+pub fn open(path: &Path) -> Result<Box<dyn Read>> {
+    decompress(File::open(path)?)
+}
+
+/// Wraps `reader` in a decoder when it starts with the magic number of gzip, zstd,
+/// bzip2 or xz, otherwise returns its content unchanged.
+pub fn decompress<'r>(mut reader: impl Read + 'r) -> Result<Box<dyn Read + 'r>> {
+    let mut magic = Vec::with_capacity(Compression::MAGIC_LEN);
+    (&mut reader)
+        .take(Compression::MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::detect(&magic);
+    let reader = Cursor::new(magic).chain(reader);
+
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        Some(Compression::Bzip2) => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        Some(Compression::Xz) => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        None => Box::new(reader),
+    })
+}
+
+/// Calls `f` with the path and the decompressed content of every JSON entry of the
+/// archive, in the order they are stored. Other entries are skipped.
+pub fn for_each_entry<F>(path: &Path, mut f: F) -> Result<()>
+where
+    F: FnMut(&str, &mut dyn Read) -> Result<()>,
+{
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    match Archive::detect(name) {
+        Some(Archive::Zip) => {
+            let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+            for i in 0..archive.len() {
+                let entry = archive.by_index(i)?;
+                let entry_path = entry.name().to_string();
+                if entry.is_dir() || !is_json(&entry_path) {
+                    continue;
+                }
+                let mut content = decompress(entry)
+                    .with_context(|| format!("Failed to read archive entry '{}'", entry_path))?;
+                f(&entry_path, &mut content)?;
+            }
+        }
+        Some(Archive::Tar) => {
+            let mut archive = tar::Archive::new(open(path)?);
+            for entry in archive.entries()? {
+                let entry = entry?;
+                let entry_path = entry.path()?.to_string_lossy().into_owned();
+                if !entry.header().entry_type().is_file() || !is_json(&entry_path) {
+                    continue;
+                }
+                let mut content = decompress(entry)
+                    .with_context(|| format!("Failed to read archive entry '{}'", entry_path))?;
+                f(&entry_path, &mut content)?;
+            }
+        }
+        None => anyhow::bail!("'{}' is not a supported archive", path.display()),
+    }
+    Ok(())
+}
@@ -1,2 +1,6 @@
 pub mod config;
+pub mod input;
 pub mod parser;
+pub mod schema;
+pub mod selector;
+pub mod writer;
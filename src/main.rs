@@ -1,46 +1,224 @@
-use anyhow::Result;
-use std::env;
+use anyhow::{Context, Result};
+use clap::Parser as _;
+use serde_json::{json, Value};
 use std::fs;
-use std::path::PathBuf;
-use walkdir::WalkDir;
+use std::path::{Path, PathBuf};
+use std::thread;
 
-use json2csv_processor::config::{Config, InputType};
-use json2csv_processor::parser;
+use json2csv_processor::config::{Config, ErrorPolicy, InputType, SchemaDriftPolicy};
+use json2csv_processor::schema::State;
+use json2csv_processor::{input, parser};
+
+/// Keboola Connection processor converting JSON files to CSV tables.
+#[derive(Debug, clap::Parser)]
+#[command(version, about)]
+struct Args {
+    /// Data directory with the Keboola component layout (`config.json`, `in/`, `out/`)
+    #[arg(long, env = "KBC_DATADIR", default_value = "/data")]
+    data_dir: PathBuf,
+
+    /// Configuration file, defaults to `<data-dir>/config.json`
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Input directory, defaults to `<data-dir>/in/files` or `<data-dir>/in/tables` by `in_type`;
+    /// the previous `state.json` is read from its parent directory
+    #[arg(long)]
+    input: Option<PathBuf>,
+
+    /// Output directory for tables, defaults to `<data-dir>/out/tables`; `state.json` and
+    /// quarantined `files/failed` are written to its parent directory
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Worker threads converting files in parallel, `0` for one per CPU core;
+    /// overrides the `threads` parameter
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Print the inferred tables and columns without writing any output
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print resolved paths and per-table row counts
+    #[arg(short, long)]
+    verbose: bool,
+}
 
 fn main() -> Result<()> {
-    let data_dir = env::var("KBC_DATADIR").unwrap_or_else(|_| "/data".to_string());
+    let args = Args::parse();
+    let data_dir = &args.data_dir;
 
-    let config_path = PathBuf::from(&data_dir).join("config.json");
-    let config: Config = serde_json::from_str(&fs::read_to_string(&config_path)?)?;
+    let config_path = args
+        .config
+        .clone()
+        .unwrap_or_else(|| data_dir.join("config.json"));
+    let config_content = fs::read_to_string(&config_path)
+        .with_context(|| format!("Failed to read config {}", config_path.display()))?;
+    let config: Config = serde_json::from_str(&config_content)?;
+    config.validate()?;
 
-    let input_dir = match config.parameters.in_type {
-        InputType::Tables => PathBuf::from(&data_dir).join("in/tables"),
-        InputType::Files => PathBuf::from(&data_dir).join("in/files"),
-    };
+    let input_dir = args
+        .input
+        .clone()
+        .unwrap_or_else(|| match config.parameters.in_type {
+            InputType::Tables => data_dir.join("in/tables"),
+            InputType::Files => data_dir.join("in/files"),
+        });
 
-    let output_dir = PathBuf::from(&data_dir).join("out/tables");
+    let output_dir = args
+        .output
+        .clone()
+        .unwrap_or_else(|| data_dir.join("out/tables"));
+
+    if args.verbose {
+        println!("Config: {}", config_path.display());
+        println!("Input directory: {}", input_dir.display());
+        println!("Output directory: {}", output_dir.display());
+    }
 
     // Create output directory if it doesn't exist
-    fs::create_dir_all(&output_dir)?;
+    if !args.dry_run {
+        fs::create_dir_all(&output_dir)?;
+    }
+
+    let on_error = config.parameters.on_error;
+    let schema_drift = config.parameters.schema_drift;
+    // Like `in/state.json` and `out/files` next to `in/files` and `out/tables`
+    let in_state_path = sibling(&input_dir, "state.json");
+    let out_state_path = sibling(&output_dir, "state.json");
+    let failed_dir = sibling(&output_dir, "files/failed");
+
+    let input_files = input::list_files(&input_dir, &config.parameters)?;
+
+    let threads = match args.threads.or(config.parameters.threads) {
+        Some(0) => thread::available_parallelism().map_or(1, usize::from),
+        Some(threads) => threads,
+        None => 1,
+    };
+    if args.verbose {
+        println!("Threads: {}", threads);
+    }
 
     // Process all JSON files in the input directory
-    let mut parser = parser::Parser::new(config, output_dir);
+    let mut parser = parser::Parser::new(config, output_dir).with_dry_run(args.dry_run);
+    let mut processed = 0;
+    let mut failed = 0;
 
-    for entry in WalkDir::new(&input_dir) {
-        let entry = entry?;
-        let path = entry.path();
+    parser.process_files(&input_files, threads, |path, result| {
+        println!("Processing file: {}", path.display());
+        let Err(e) = result else {
+            processed += 1;
+            return Ok(());
+        };
+        eprintln!("Error processing file {}: {:#}", path.display(), e);
+        failed += 1;
+        match on_error {
+            ErrorPolicy::Fail => return Err(e),
+            ErrorPolicy::Skip => {}
+            ErrorPolicy::Quarantine if args.dry_run => {}
+            ErrorPolicy::Quarantine => {
+                let relative_path = path.strip_prefix(&input_dir).unwrap_or(path);
+                quarantine(path, &failed_dir.join(relative_path), &e)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    println!("Processed {} files, {} failed", processed, failed);
+
+    let state = parser.state();
+    if schema_drift != SchemaDriftPolicy::Ignore {
+        let result = check_schema_drift(&in_state_path, &state, schema_drift);
+        if result.is_err() {
+            // Streaming has already written the rows
+            parser.discard_tables()?;
+        }
+        result?;
+    }
 
-        if path.is_file() && path.extension().is_some_and(|ext| ext == "json") {
-            println!("Processing file: {}", path.display());
-            if let Err(e) = parser.process_file(path) {
-                eprintln!("Error processing file {}: {}", path.display(), e);
-                return Err(e);
+    if args.dry_run {
+        for table in parser.tables() {
+            println!("Table {} ({} rows)", table.name, table.rows);
+            for column in table.columns {
+                println!("  {}", column);
             }
         }
+        return Ok(());
     }
 
     // Write all tables
     parser.write_tables()?;
 
+    if let Some(parent) = out_state_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&out_state_path, serde_json::to_string_pretty(&state)?)?;
+
+    if args.verbose {
+        for table in parser.tables() {
+            println!(
+                "Wrote table {}: {} columns, {} rows",
+                table.name,
+                table.columns.len(),
+                table.rows
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves `path` in the parent directory of `dir`.
+fn sibling(dir: &Path, path: &str) -> PathBuf {
+    dir.parent().unwrap_or(dir).join(path)
+}
+
+/// Reports how the table schemas changed since the run that saved `state_path`.
+fn check_schema_drift(state_path: &Path, state: &State, policy: SchemaDriftPolicy) -> Result<()> {
+    if !state_path.exists() {
+        return Ok(());
+    }
+    let content = fs::read_to_string(state_path)
+        .with_context(|| format!("Failed to read state {}", state_path.display()))?;
+    let previous: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid state {}", state_path.display()))?;
+    // Keboola passes `{}` before the first run, and state saved by another
+    // component has no tables either
+    if previous.get("tables").is_none() {
+        return Ok(());
+    }
+    let previous: State = serde_json::from_value(previous)
+        .with_context(|| format!("Invalid state {}", state_path.display()))?;
+
+    let changes = state.changes_since(&previous);
+    for change in &changes {
+        eprintln!("Schema drift: {}", change);
+    }
+    if policy == SchemaDriftPolicy::Fail && !changes.is_empty() {
+        anyhow::bail!(
+            "Schema changed since the previous run in {} places, schema_drift is 'fail'",
+            changes.len()
+        );
+    }
+    Ok(())
+}
+
+/// Copies a file that failed to convert next to a `<file>.error.json` report.
+fn quarantine(path: &Path, destination: &Path, error: &anyhow::Error) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(path, destination)?;
+
+    let mut report_path = destination.as_os_str().to_owned();
+    report_path.push(".error.json");
+    let report = json!({
+        "file": path.display().to_string(),
+        "error": format!("{:#}", error),
+    });
+    fs::write(report_path, serde_json::to_string_pretty(&report)?)?;
+
+    println!("Quarantined file: {}", destination.display());
     Ok(())
 }
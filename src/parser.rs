@@ -1,21 +1,211 @@
-use crate::config::{Config, MappingType, TableMapping};
-use anyhow::Result;
-use serde_json::Value;
-use std::collections::HashMap;
+use crate::config::{
+    BooleanFormat, Config, FormatOptions, InputFormat, MappingType, Parameters, TypeConflictPolicy,
+};
+use crate::input;
+use crate::schema::{ColumnType, State, TableSchema};
+use crate::selector::{select, Segment, Selector};
+use crate::writer::TableWriter;
+use anyhow::{bail, Context, Result};
+use indexmap::IndexMap;
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::io::Write;
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+pub(crate) const ROOT_TABLE: &str = "root";
+const PARENT_ID_COLUMN: &str = "JSON_parentId";
+const FILE_NAME_COLUMN: &str = "keboola_file_name_col";
+/// Column, and mapping key, holding the items of arrays of scalars.
+const SCALAR_VALUE_COLUMN: &str = "data";
 
 pub struct Parser {
     config: Config,
     output_dir: PathBuf,
     tables: HashMap<String, TableData>,
+    shapes: Shapes,
+    /// Only infer table schemas, rows are neither kept nor written.
+    dry_run: bool,
+    /// Only learn the key shapes of the input, no rows are produced.
+    learning: bool,
 }
 
+/// Inferred schema of one output table.
 #[derive(Debug)]
+pub struct TableSummary<'a> {
+    pub name: &'a str,
+    pub columns: &'a [String],
+    pub rows: usize,
+}
+
 struct TableData {
     headers: Vec<String>,
-    rows: Vec<HashMap<String, String>>,
+    /// Inferred type of each header, `None` while a column only held empty values.
+    types: Vec<Option<ColumnType>>,
+    row_count: usize,
+    /// Values in header order; rows added before a column appeared are shorter.
+    rows: Vec<Vec<String>>,
+    /// Rows are written as soon as they are produced when streaming.
+    writer: Option<TableWriter>,
+}
+
+impl TableData {
+    /// Appends the rows of a table parsed separately, adding its new columns and
+    /// moving its values into this table's header order.
+    fn append(&mut self, other: TableData) {
+        let positions: Vec<usize> = other
+            .headers
+            .into_iter()
+            .zip(other.types)
+            .map(|(header, column_type)| {
+                let position = match self.headers.iter().position(|h| *h == header) {
+                    Some(position) => position,
+                    None => {
+                        self.headers.push(header);
+                        self.types.push(None);
+                        self.headers.len() - 1
+                    }
+                };
+                if let Some(other_type) = column_type {
+                    let merged = &mut self.types[position];
+                    *merged = Some(merged.map_or(other_type, |t| t.merge(other_type)));
+                }
+                position
+            })
+            .collect();
+
+        self.row_count += other.row_count;
+        if positions
+            .iter()
+            .enumerate()
+            .all(|(i, &position)| i == position)
+        {
+            self.rows.extend(other.rows);
+            return;
+        }
+        for row in other.rows {
+            let mut record = vec![String::new(); self.headers.len()];
+            for (value, &position) in row.into_iter().zip(&positions) {
+                record[position] = value;
+            }
+            self.rows.push(record);
+        }
+    }
+}
+
+/// State of a table before an input file was processed.
+struct TableCheckpoint {
+    columns: usize,
+    types: Vec<Option<ColumnType>>,
+    rows: usize,
+    row_count: usize,
+    /// Position in the output file when streaming.
+    written: Option<u64>,
+}
+
+/// Keboola output table manifest written next to each `<table>.csv`.
+#[derive(Debug, Serialize)]
+struct TableManifest<'a> {
+    incremental: bool,
+    delimiter: char,
+    enclosure: char,
+    primary_key: Vec<&'a str>,
+    columns: &'a [String],
+    column_metadata: IndexMap<&'a str, Vec<ColumnMetadata>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ColumnMetadata {
+    key: &'static str,
+    value: &'static str,
+}
+
+/// Kinds of values seen under each `.` separated JSON path, collected from all the
+/// input in a pass before any rows are produced so that keys with inconsistent
+/// shapes are written the same way in every row.
+#[derive(Default, Clone)]
+struct Shapes {
+    arrays: HashSet<String>,
+    objects: HashSet<String>,
+    scalars: HashSet<String>,
+}
+
+impl Shapes {
+    fn learn(&mut self, value: &Value, path: &str) {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    self.learn(item, path);
+                }
+            }
+            Value::Object(obj) => {
+                for (key, val) in obj {
+                    let child = format!("{}.{}", path, key);
+                    match val {
+                        Value::Array(_) => self.arrays.insert(child.clone()),
+                        Value::Object(_) => self.objects.insert(child.clone()),
+                        Value::Null => false,
+                        _ => self.scalars.insert(child.clone()),
+                    };
+                    self.learn(val, &child);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds the shapes learned by another parser, in any order.
+    fn extend(&mut self, other: Shapes) {
+        self.arrays.extend(other.arrays);
+        self.objects.extend(other.objects);
+        self.scalars.extend(other.scalars);
+    }
+
+    /// Whether `path` holds both scalars and objects.
+    fn is_conflict(&self, path: &str) -> bool {
+        self.scalars.contains(path) && self.objects.contains(path)
+    }
+}
+
+/// Destination of the rows produced from one level of the JSON tree.
+struct TableContext<'a> {
+    name: String,
+    /// `.` separated JSON path of the table, used as the generated parent key prefix.
+    path: String,
+    /// Mapping applied to the objects of this table, `None` when columns are inferred.
+    mapping: Option<&'a IndexMap<String, MappingType>>,
+    /// Column holding the parent row identifier, `None` for the root table.
+    parent_key: Option<&'a str>,
+}
+
+/// Identifier joining child rows to their parent row, with the type of the values
+/// it comes from so that both sides of the join get the same column type.
+#[derive(Clone)]
+struct RowId {
+    value: String,
+    column_type: Option<ColumnType>,
+}
+
+/// Walks the JSON tree in lockstep with the mapping tree and collects rows.
+struct Walker<'a> {
+    params: &'a Parameters,
+    output_dir: &'a Path,
+    dry_run: bool,
+    tables: &'a mut HashMap<String, TableData>,
+    shapes: &'a Shapes,
+    file_name: &'a str,
+    /// Identifies the source record in generated keys: the file name, with the
+    /// line number appended for NDJSON input and the row number for CSV input.
+    source: &'a str,
+    /// Columns of the CSV row the record was read from, added to root rows.
+    carried: &'a [(String, String)],
 }
 
 impl Parser {
@@ -24,378 +214,1356 @@ impl Parser {
             config,
             output_dir,
             tables: HashMap::new(),
+            shapes: Shapes::default(),
+            dry_run: false,
+            learning: false,
         }
     }
 
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Tables inferred so far, ordered by name.
+    pub fn tables(&self) -> Vec<TableSummary<'_>> {
+        let mut tables: Vec<_> = self
+            .tables
+            .iter()
+            .map(|(name, data)| TableSummary {
+                name,
+                columns: &data.headers,
+                rows: data.row_count,
+            })
+            .collect();
+        tables.sort_by(|a, b| a.name.cmp(b.name));
+        tables
+    }
+
+    /// Inferred schemas of the tables, to be compared with the next run's.
+    pub fn state(&self) -> State {
+        let tables = self
+            .tables
+            .iter()
+            .map(|(name, data)| {
+                let columns = data
+                    .headers
+                    .iter()
+                    .cloned()
+                    .zip(data.types.clone())
+                    .collect();
+                (name.clone(), TableSchema { columns })
+            })
+            .collect();
+        State { tables }
+    }
+
+    /// Converts one input file. When it fails, the rows it already produced are
+    /// rolled back so that the tables only ever contain complete files.
+    ///
+    /// The file is read twice, first to learn its key shapes, unless every table is
+    /// mapped. Use `process_files` to learn them from all the files before
+    /// converting the first one.
     pub fn process_file(&mut self, input_path: &Path) -> Result<()> {
-        let file_content = std::fs::read_to_string(input_path)?;
-        let json: Value = serde_json::from_str(&file_content)?;
+        self.learn_shapes(input_path)?;
+        self.convert_file(input_path)
+    }
+
+    /// Learns the key shapes of a file without producing rows. The shapes of a file
+    /// that fails to parse are forgotten.
+    ///
+    /// Only inferred tables depend on the shapes, the file is not read when every
+    /// table is mapped.
+    fn learn_shapes(&mut self, input_path: &Path) -> Result<()> {
+        if !self.config.parameters.infers_tables() {
+            return Ok(());
+        }
+        let shapes = self.shapes.clone();
+        self.learning = true;
+        let result = self.process_file_rows(input_path);
+        self.learning = false;
+        if result.is_err() {
+            self.shapes = shapes;
+        }
+        result
+    }
+
+    fn convert_file(&mut self, input_path: &Path) -> Result<()> {
+        let checkpoints = self.checkpoint()?;
+        let result = self.process_file_rows(input_path);
+        if result.is_err() {
+            self.rollback(checkpoints)?;
+        }
+        result
+    }
+
+    /// Converts the files on up to `threads` worker threads. Each file is parsed into
+    /// tables of its own, which are appended in the order of `paths` so that the
+    /// output does not depend on which thread finishes first. `on_done` receives the
+    /// result of each file in that order and stops the processing by returning an
+    /// error.
+    ///
+    /// The key shapes of all the files are learned before the first file is
+    /// converted, so the output does not depend on `threads` either. Streaming
+    /// writes the rows as they are produced and always converts the files one by
+    /// one.
+    pub fn process_files<F>(
+        &mut self,
+        paths: &[PathBuf],
+        threads: usize,
+        mut on_done: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&Path, Result<()>) -> Result<()>,
+    {
+        if threads <= 1 || self.config.parameters.streaming {
+            // Files that fail to parse are reported when they are converted
+            for path in paths {
+                let _ = self.learn_shapes(path);
+            }
+            for path in paths {
+                let result = self.convert_file(path);
+                on_done(path, result)?;
+            }
+            return Ok(());
+        }
+
+        let workers = threads.min(paths.len());
+        let next = AtomicUsize::new(0);
+        let shapes: Vec<Shapes> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| {
+                    let next = &next;
+                    let mut worker = Parser::new(self.config.clone(), self.output_dir.clone());
+                    scope.spawn(move || {
+                        // Files that fail to parse are reported when they are converted
+                        while let Some(path) = paths.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            let _ = worker.learn_shapes(path);
+                        }
+                        worker.shapes
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("shape learning thread panicked"))
+                .collect()
+        });
+        for worker_shapes in shapes {
+            self.shapes.extend(worker_shapes);
+        }
+
+        next.store(0, Ordering::Relaxed);
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..workers {
+                let sender = sender.clone();
+                let next = &next;
+                let mut worker = Parser::new(self.config.clone(), self.output_dir.clone())
+                    .with_dry_run(self.dry_run);
+                worker.shapes = self.shapes.clone();
+                scope.spawn(move || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else {
+                        break;
+                    };
+                    let result = worker.convert_file(path);
+                    let tables = std::mem::take(&mut worker.tables);
+                    // The receiver is gone once `on_done` stopped the processing
+                    if sender.send((index, result.map(|()| tables))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            // Files finished out of order wait until the files before them are appended
+            let mut pending = BTreeMap::new();
+            let mut appended = 0;
+            for (index, result) in receiver {
+                pending.insert(index, result);
+                while let Some(result) = pending.remove(&appended) {
+                    on_done(&paths[appended], result.map(|tables| self.append(tables)))?;
+                    appended += 1;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes the files of the tables written while streaming, leaving no partial
+    /// tables behind when the run is aborted.
+    pub fn discard_tables(&mut self) -> Result<()> {
+        for data in self.tables.values_mut() {
+            if let Some(writer) = data.writer.take() {
+                writer.discard()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the rows of tables parsed by another parser after the rows of these.
+    fn append(&mut self, tables: HashMap<String, TableData>) {
+        for (name, data) in tables {
+            match self.tables.entry(name) {
+                Entry::Occupied(mut entry) => entry.get_mut().append(data),
+                Entry::Vacant(entry) => {
+                    entry.insert(data);
+                }
+            }
+        }
+    }
 
+    fn process_file_rows(&mut self, input_path: &Path) -> Result<()> {
         let file_name = input_path
             .file_name()
             .and_then(|n| n.to_str())
             .unwrap_or("")
             .to_string();
 
-        if self.config.parameters.add_file_name {
-            let root_table = self
-                .tables
-                .entry("root".to_string())
-                .or_insert_with(|| TableData {
-                    headers: vec![
-                        "id".to_string(),
-                        "name".to_string(),
-                        "keboola_file_name_col".to_string(),
-                    ],
-                    rows: Vec::new(),
-                });
-            if !root_table
-                .headers
-                .contains(&"keboola_file_name_col".to_string())
+        if input::is_archive(input_path) {
+            // Entries are named by their own file name, while generated keys tell
+            // apart entries of the same name from different archives
+            return input::for_each_entry(input_path, |entry_path, content| {
+                let entry_name = entry_path.rsplit('/').next().unwrap_or(entry_path);
+                let source = format!("{}/{}", file_name, entry_path);
+                self.process_input(content, entry_name, &source)
+                    .with_context(|| format!("Failed to process archive entry '{}'", entry_path))
+            });
+        }
+        self.process_input(input::open(input_path)?, &file_name, &file_name)
+    }
+
+    /// Converts the decompressed content of one file, with `source` identifying it
+    /// in generated keys.
+    fn process_input(
+        &mut self,
+        mut content: impl Read,
+        file_name: &str,
+        source: &str,
+    ) -> Result<()> {
+        if self.config.parameters.csv_input.is_some() && input::is_csv(file_name) {
+            return self.process_csv(content, file_name, source);
+        }
+        if self.input_format(file_name) == InputFormat::Ndjson {
+            return self.process_ndjson(content, file_name, source);
+        }
+        if self.config.parameters.streaming {
+            return self.stream_file(content, file_name, source);
+        }
+
+        let mut file_content = String::new();
+        content.read_to_string(&mut file_content)?;
+        let json: Value = serde_json::from_str(&file_content)?;
+
+        for (root, selector) in self.root_selectors()?.iter().enumerate() {
+            let root_values = self.get_root_node(&json, selector)?;
+            self.process_items(root, &root_values, file_name, source, &[], 0)?;
+        }
+        Ok(())
+    }
+
+    /// Parses the file incrementally, converting the items of the arrays at the root
+    /// nodes one at a time instead of loading the whole document.
+    fn stream_file(&mut self, content: impl Read, file_name: &str, source: &str) -> Result<()> {
+        let selectors = self.root_selectors()?;
+        let paths = selectors
+            .iter()
+            .map(Selector::segments)
+            .enumerate()
+            .collect();
+
+        let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(content));
+        let mut indices = vec![0; selectors.len()];
+        let mut state = StreamState {
+            on_item: |root: usize, item: Value| {
+                self.process_items(root, &[&item], file_name, source, &[], indices[root])?;
+                indices[root] += 1;
+                Ok(())
+            },
+            found: vec![false; selectors.len()],
+        };
+        RootNodeSeed {
+            paths,
+            state: &mut state,
+        }
+        .deserialize(&mut deserializer)?;
+        deserializer.end()?;
+
+        for (selector, found) in selectors.iter().zip(state.found) {
+            if !found && !selector.has_wildcard() {
+                anyhow::bail!("Root node path '{}' not found in JSON", selector);
+            }
+        }
+        Ok(())
+    }
+
+    /// Selectors of `root_nodes`, or of `root_node` when no list is configured.
+    fn root_selectors(&self) -> Result<Vec<Selector>> {
+        let params = &self.config.parameters;
+        let root_nodes: Vec<&str> = if params.root_nodes.is_empty() {
+            vec![&params.root_node]
+        } else {
+            params
+                .root_nodes
+                .iter()
+                .map(|r| r.root_node.as_str())
+                .collect()
+        };
+        root_nodes
+            .into_iter()
+            .map(|root_node| {
+                root_node
+                    .parse()
+                    .with_context(|| format!("Invalid root node path '{}'", root_node))
+            })
+            .collect()
+    }
+
+    fn checkpoint(&mut self) -> Result<HashMap<String, TableCheckpoint>> {
+        let mut checkpoints = HashMap::new();
+        for (name, data) in &mut self.tables {
+            let written = match &mut data.writer {
+                Some(writer) => Some(writer.checkpoint()?),
+                None => None,
+            };
+            checkpoints.insert(
+                name.clone(),
+                TableCheckpoint {
+                    columns: data.headers.len(),
+                    types: data.types.clone(),
+                    rows: data.rows.len(),
+                    row_count: data.row_count,
+                    written,
+                },
+            );
+        }
+        Ok(checkpoints)
+    }
+
+    fn rollback(&mut self, mut checkpoints: HashMap<String, TableCheckpoint>) -> Result<()> {
+        let created: Vec<String> = self
+            .tables
+            .keys()
+            .filter(|name| !checkpoints.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in created {
+            if let Some(writer) = self.tables.remove(&name).and_then(|data| data.writer) {
+                writer.discard()?;
+            }
+        }
+
+        for (name, data) in &mut self.tables {
+            let Some(checkpoint) = checkpoints.remove(name) else {
+                continue;
+            };
+            data.headers.truncate(checkpoint.columns);
+            data.types = checkpoint.types;
+            data.rows.truncate(checkpoint.rows);
+            data.row_count = checkpoint.row_count;
+            if let (Some(writer), Some(position)) = (&mut data.writer, checkpoint.written) {
+                writer.rollback(position)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Format of a file by its name, compression extensions aside.
+    fn input_format(&self, file_name: &str) -> InputFormat {
+        match self.config.parameters.input_format {
+            InputFormat::Auto => match Path::new(input::strip_compression(file_name))
+                .extension()
+                .and_then(|ext| ext.to_str())
             {
-                root_table.headers.push("keboola_file_name_col".to_string());
+                Some("jsonl" | "ndjson") => InputFormat::Ndjson,
+                _ => InputFormat::Json,
+            },
+            format => format,
+        }
+    }
+
+    /// Treats every non-empty line as one JSON document with `root_node` applied to it.
+    fn process_ndjson(&mut self, content: impl Read, file_name: &str, source: &str) -> Result<()> {
+        let selectors = self.root_selectors()?;
+        let reader = BufReader::new(content);
+        for (i, line) in reader.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.with_context(|| format!("Failed to read line {}", line_number))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let json: Value = serde_json::from_str(&line)
+                .with_context(|| format!("Invalid JSON on line {}", line_number))?;
+            let source = format!("{}:{}", source, line_number);
+            for (root, selector) in selectors.iter().enumerate() {
+                let root_values = self
+                    .get_root_node(&json, selector)
+                    .with_context(|| format!("Failed to process line {}", line_number))?;
+                self.process_items(root, &root_values, file_name, &source, &[], 0)
+                    .with_context(|| format!("Failed to process line {}", line_number))?;
             }
         }
+        Ok(())
+    }
 
-        let root_value = self.get_root_node(&json, &self.config.parameters.root_node)?;
-        self.process_value(root_value, "root".to_string(), None, &file_name)?;
+    /// Treats the `csv_input.json_column` value of every row as one JSON document with
+    /// `root_node` applied to it, and copies the carried columns into its root rows.
+    fn process_csv(&mut self, content: impl Read, file_name: &str, source: &str) -> Result<()> {
+        let Some(csv_input) = self.config.parameters.csv_input.clone() else {
+            bail!("csv_input is not configured");
+        };
+        let selectors = self.root_selectors()?;
+        let mut reader = csv::Reader::from_reader(content);
+        let headers = reader.headers()?.clone();
+        let position = |column: &str| {
+            headers
+                .iter()
+                .position(|header| header == column)
+                .with_context(|| format!("Column '{}' not found in CSV header", column))
+        };
+        let json_index = position(&csv_input.json_column)?;
+        let carried_indices = csv_input
+            .carry_columns
+            .iter()
+            .map(|column| position(column))
+            .collect::<Result<Vec<_>>>()?;
 
+        for (i, record) in reader.records().enumerate() {
+            let row_number = i + 1;
+            let record = record.with_context(|| format!("Failed to read row {}", row_number))?;
+            let cell = record.get(json_index).unwrap_or("");
+            if cell.trim().is_empty() {
+                continue;
+            }
+
+            let json: Value = serde_json::from_str(cell)
+                .with_context(|| format!("Invalid JSON in row {}", row_number))?;
+            let carried: Vec<(String, String)> = csv_input
+                .carry_columns
+                .iter()
+                .zip(&carried_indices)
+                .map(|(column, &index)| {
+                    (column.clone(), record.get(index).unwrap_or("").to_string())
+                })
+                .collect();
+            let source = format!("{}:{}", source, row_number);
+            for (root, selector) in selectors.iter().enumerate() {
+                let root_values = self
+                    .get_root_node(&json, selector)
+                    .with_context(|| format!("Failed to process row {}", row_number))?;
+                self.process_items(root, &root_values, file_name, &source, &carried, 0)
+                    .with_context(|| format!("Failed to process row {}", row_number))?;
+            }
+        }
         Ok(())
     }
 
-    pub fn process_value(
+    /// Converts a value already selected by `root_node` into rows of the root table family.
+    pub fn process_value(&mut self, value: &Value, file_name: &str) -> Result<()> {
+        self.process_items(0, &[value], file_name, file_name, &[], 0)
+    }
+
+    /// Converts the nodes matched by the `root`-th root node: the items of matched
+    /// arrays, and matched objects themselves, are concatenated into its root table
+    /// and numbered from `index`.
+    fn process_items(
         &mut self,
-        value: &Value,
-        table_name: String,
-        parent_path: Option<String>,
+        root: usize,
+        values: &[&Value],
         file_name: &str,
+        source: &str,
+        carried: &[(String, String)],
+        mut index: usize,
     ) -> Result<()> {
-        match value {
-            Value::Object(obj) => {
-                let mut row = HashMap::new();
-                let mut headers = Vec::new();
+        let params = &self.config.parameters;
+        let root = match params.root_nodes.get(root) {
+            Some(root_node) => TableContext {
+                name: root_node.destination.clone(),
+                path: if root_node.root_node.is_empty() {
+                    root_node.destination.clone()
+                } else {
+                    root_node.root_node.clone()
+                },
+                mapping: Some(&root_node.mapping),
+                parent_key: None,
+            },
+            None => TableContext {
+                name: ROOT_TABLE.to_string(),
+                path: if params.root_node.is_empty() {
+                    ROOT_TABLE.to_string()
+                } else {
+                    params.root_node.clone()
+                },
+                mapping: Some(&params.mapping),
+                parent_key: None,
+            },
+        };
+        for value in values {
+            self.shapes.learn(value, &root.path);
+        }
+        if self.learning {
+            return Ok(());
+        }
+        let mut walker = Walker {
+            params,
+            output_dir: &self.output_dir,
+            dry_run: self.dry_run,
+            tables: &mut self.tables,
+            shapes: &self.shapes,
+            file_name,
+            source,
+            carried,
+        };
+        for value in values {
+            let items = match value {
+                Value::Array(items) => items.iter().collect(),
+                _ => vec![*value],
+            };
+            for item in items {
+                walker.walk(item, &root, None, index)?;
+                index += 1;
+            }
+        }
+        Ok(())
+    }
 
-                // Add file name column if configured and at root level
-                if self.config.parameters.add_file_name
-                    && (parent_path.is_none() || parent_path.as_deref() == Some("root"))
-                {
-                    headers.push("keboola_file_name_col".to_string());
-                    row.insert(
-                        "keboola_file_name_col".to_string(),
-                        format!("{} ", file_name),
-                    );
-                }
+    fn get_root_node<'a>(&self, json: &'a Value, selector: &Selector) -> Result<Vec<&'a Value>> {
+        let matches = selector.select(json);
+        if matches.is_empty() && !selector.has_wildcard() {
+            anyhow::bail!("Root node path '{}' not found in JSON", selector);
+        }
+        Ok(matches)
+    }
 
-                // Process each field in the object
-                for (key, val) in obj {
-                    match val {
-                        Value::Object(_) => continue,
-                        Value::Array(arr) => {
-                            // Process array items as a separate table
-                            for (i, item) in arr.iter().enumerate() {
-                                let parent_id = if !self.config.parameters.mapping.is_empty() {
-                                    // If we have a mapping and this object has an ID field, use that
-                                    if let Some(id) = obj.get("id") {
-                                        // Add trailing space to IDs ending in certain digits
-                                        let id_str = self.format_value(id);
-                                        if id_str.trim().ends_with('2') {
-                                            format!("{} ", id_str.trim())
-                                        } else {
-                                            id_str
-                                        }
-                                    } else {
-                                        let base_id = format!("{}_{}", key, i);
-                                        if i == 1 {
-                                            format!("{} ", base_id)
-                                        } else {
-                                            base_id
-                                        }
-                                    }
-                                } else {
-                                    let base_id = format!("{}_{}", key, i);
-                                    if i == 1 {
-                                        format!("{} ", base_id)
-                                    } else {
-                                        base_id
-                                    }
-                                };
-                                let child_table = if !self.config.parameters.mapping.is_empty() {
-                                    if let Some(mapping) =
-                                        self.config.parameters.mapping.values().next()
-                                    {
-                                        match mapping {
-                                            MappingType::Table(table_mapping) => {
-                                                table_mapping.destination.clone()
-                                            }
-                                            _ => key.clone(),
-                                        }
-                                    } else {
-                                        key.clone()
-                                    }
-                                } else {
-                                    key.clone()
-                                };
-                                self.process_value(item, child_table, Some(parent_id), file_name)?;
-                            }
-                        }
-                        _ => {
-                            let header = if table_name == "root" {
-                                key.clone()
-                            } else if key == "id" {
-                                "item_id".to_string()
-                            } else {
-                                key.clone()
-                            };
-                            headers.push(header.clone());
-                            row.insert(header, self.format_value(val));
-                        }
+    /// Collects primary key columns per destination table from the mapping tree.
+    fn primary_keys(&self) -> HashMap<String, HashSet<String>> {
+        let params = &self.config.parameters;
+        let mut keys = HashMap::new();
+        collect_primary_keys(&params.mapping, ROOT_TABLE, &mut keys);
+        for root_node in &params.root_nodes {
+            collect_primary_keys(&root_node.mapping, &root_node.destination, &mut keys);
+        }
+        keys
+    }
+
+    fn write_manifest(
+        &self,
+        table_name: &str,
+        data: &TableData,
+        primary_keys: Option<&HashSet<String>>,
+    ) -> Result<()> {
+        // Keep the key order stable by following the column order
+        let primary_key = data
+            .headers
+            .iter()
+            .filter(|header| primary_keys.is_some_and(|keys| keys.contains(*header)))
+            .map(String::as_str)
+            .collect();
+        // Columns that only held empty values are typed as strings
+        let column_metadata = data
+            .headers
+            .iter()
+            .zip(&data.types)
+            .map(|(header, column_type)| {
+                let basetype = column_type.map_or("STRING", ColumnType::basetype);
+                let metadata = ColumnMetadata {
+                    key: "KBC.datatype.basetype",
+                    value: basetype,
+                };
+                (header.as_str(), vec![metadata])
+            })
+            .collect();
+        let manifest = TableManifest {
+            incremental: self.config.parameters.incremental,
+            delimiter: self.config.parameters.csv.delimiter,
+            enclosure: self.config.parameters.csv.quote,
+            primary_key,
+            columns: &data.headers,
+            column_metadata,
+        };
+
+        let extension = self.config.parameters.compression.format.extension();
+        let manifest_path = self
+            .output_dir
+            .join(format!("{}.csv{}.manifest", table_name, extension));
+        fs::write(manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+        Ok(())
+    }
+
+    pub fn write_tables(&mut self) -> Result<()> {
+        let primary_keys = self.primary_keys();
+        for (table_name, data) in &mut self.tables {
+            let writer = match data.writer.take() {
+                Some(writer) => writer,
+                None => {
+                    let output_path = self.output_dir.join(format!("{}.csv", table_name));
+                    let mut writer = TableWriter::create(
+                        &output_path,
+                        &data.headers,
+                        &self.config.parameters.csv,
+                    )?;
+                    for row in &data.rows {
+                        let padding = data.headers.len() - row.len();
+                        writer.write_row(
+                            row.iter()
+                                .map(String::as_str)
+                                .chain(std::iter::repeat("").take(padding)),
+                        )?;
                     }
+                    writer
                 }
+            };
+            let params = &self.config.parameters;
+            writer.finish(&data.headers, &params.compression, &params.slicing)?;
+        }
 
-                // Add parent ID if this is a child table
-                if let Some(parent_id) = parent_path {
-                    let parent_id_header = if !self.config.parameters.mapping.is_empty() {
-                        if let Some(mapping) = self.config.parameters.mapping.values().next() {
-                            match mapping {
-                                MappingType::Table(table_mapping) => {
-                                    if let Some(parent_key) = &table_mapping.parent_key {
-                                        parent_key.destination.clone()
-                                    } else {
-                                        "JSON_parentId".to_string()
-                                    }
-                                }
-                                _ => "JSON_parentId".to_string(),
-                            }
-                        } else {
-                            "JSON_parentId".to_string()
-                        }
-                    } else {
-                        "JSON_parentId".to_string()
-                    };
-                    headers.push(parent_id_header.clone());
-                    row.insert(parent_id_header, parent_id);
+        for (table_name, data) in &self.tables {
+            self.write_manifest(table_name, data, primary_keys.get(table_name))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Walker<'a> {
+    fn walk(
+        &mut self,
+        value: &Value,
+        table: &TableContext<'a>,
+        parent_id: Option<&RowId>,
+        index: usize,
+    ) -> Result<()> {
+        match value {
+            Value::Object(obj) => match table.mapping {
+                Some(mapping) if !mapping.is_empty() => {
+                    self.walk_mapped_object(obj, mapping, table, parent_id, index)
                 }
+                _ => self.walk_object(obj, table, parent_id, index),
+            },
+            Value::Array(arr) => {
+                for (i, item) in arr.iter().enumerate() {
+                    self.walk(item, table, parent_id, i)?;
+                }
+                Ok(())
+            }
+            _ => self.walk_scalar(value, table, parent_id, index),
+        }
+    }
 
-                // Initialize or update the table
-                let table = self.tables.entry(table_name.clone()).or_insert_with(|| {
-                    let default_headers =
-                        if table_name == "root" && !self.config.parameters.root_node.is_empty() {
-                            vec!["id".to_string()]
-                        } else if table_name == "root" {
-                            vec!["id".to_string(), "name".to_string()]
-                        } else if !self.config.parameters.mapping.is_empty() {
-                            if let Some(mapping) = self.config.parameters.mapping.values().next() {
-                                match mapping {
-                                    MappingType::Table(table_mapping) => {
-                                        let mut headers =
-                                            vec!["item_id".to_string(), "quantity".to_string()];
-                                        if let Some(parent_key) = &table_mapping.parent_key {
-                                            headers.push(parent_key.destination.clone());
-                                        } else {
-                                            headers.push("JSON_parentId".to_string());
-                                        }
-                                        headers
-                                    }
-                                    _ => vec![
-                                        "item_id".to_string(),
-                                        "quantity".to_string(),
-                                        "JSON_parentId".to_string(),
-                                    ],
-                                }
-                            } else {
-                                vec![
-                                    "item_id".to_string(),
-                                    "quantity".to_string(),
-                                    "JSON_parentId".to_string(),
-                                ]
-                            }
-                        } else {
-                            vec![
-                                "item_id".to_string(),
-                                "quantity".to_string(),
-                                "JSON_parentId".to_string(),
-                            ]
-                        };
-                    TableData {
-                        headers: default_headers,
-                        rows: Vec::new(),
-                    }
-                });
+    /// Array items that are not objects become rows with a single `data` column,
+    /// which mappings refer to by the `data` key.
+    fn walk_scalar(
+        &mut self,
+        value: &Value,
+        table: &TableContext<'a>,
+        parent_id: Option<&RowId>,
+        index: usize,
+    ) -> Result<()> {
+        match table.mapping {
+            Some(mapping) if !mapping.is_empty() => {
+                let obj = Map::from_iter([(SCALAR_VALUE_COLUMN.to_string(), value.clone())]);
+                self.walk_mapped_object(&obj, mapping, table, parent_id, index)
+            }
+            _ => {
+                let row = HashMap::from([(
+                    SCALAR_VALUE_COLUMN.to_string(),
+                    format_value(value, &self.params.format),
+                )]);
+                let types =
+                    HashMap::from([(SCALAR_VALUE_COLUMN.to_string(), ColumnType::of_value(value))]);
+                self.add_row(
+                    table,
+                    vec![SCALAR_VALUE_COLUMN.to_string()],
+                    row,
+                    types,
+                    parent_id,
+                )
+            }
+        }
+    }
 
-                // Update headers if needed
-                for header in headers {
-                    if !table.headers.contains(&header) {
-                        table.headers.push(header);
-                    }
-                }
+    /// Infers the columns and child tables of an object without a mapping.
+    fn walk_object(
+        &mut self,
+        obj: &Map<String, Value>,
+        table: &TableContext<'a>,
+        parent_id: Option<&RowId>,
+        index: usize,
+    ) -> Result<()> {
+        let mut row = HashMap::new();
+        let mut types = HashMap::new();
+        let mut headers = Vec::new();
+        let mut row_id = None;
+
+        let mut fields = Vec::new();
+        self.flatten(obj, &table.path, None, 0, &mut fields)?;
 
-                // Fill in missing values with empty strings
-                let mut row_with_all_headers = HashMap::new();
-                for header in &table.headers {
-                    row_with_all_headers
-                        .insert(header.clone(), row.get(header).cloned().unwrap_or_default());
+        for (column, path, val) in fields {
+            let child_path = format!("{}.{}", table.path, path);
+            let items = match val {
+                Value::Array(arr) => arr.as_slice(),
+                // A single object under a key that holds arrays elsewhere is a one-item array
+                Value::Object(_) if self.shapes.arrays.contains(&child_path) => {
+                    std::slice::from_ref(val)
                 }
+                _ => {
+                    headers.push(column.clone());
+                    types.insert(column.clone(), ColumnType::of_value(val));
+                    row.insert(column, format_value(val, &self.params.format));
+                    continue;
+                }
+            };
+
+            // Process array items as a separate table, the parent row keeps
+            // the join key in a column named after the array
+            let row_id = row_id
+                .get_or_insert_with(|| self.row_id(obj, table, parent_id, index, &[]))
+                .clone();
+            headers.push(column.clone());
+            types.insert(column.clone(), row_id.column_type);
+            row.insert(column.clone(), row_id.value.clone());
 
-                table.rows.push(row_with_all_headers);
+            // Child tables are named after their parent table, e.g. `root_items`
+            // and `root_items_tags`, so equal keys at different depths stay apart
+            let child = TableContext {
+                name: format!("{}_{}", table.name, column),
+                path: child_path,
+                mapping: None,
+                parent_key: Some(PARENT_ID_COLUMN),
+            };
+            for (i, item) in items.iter().enumerate() {
+                self.walk(item, &child, Some(&row_id), i)?;
             }
-            Value::Array(arr) => {
-                for (i, item) in arr.iter().enumerate() {
-                    let parent_id = if let Some(path) = &parent_path {
-                        format!("{}_{}", path, i)
-                    } else {
-                        format!("{}_{}", table_name, i)
-                    };
-                    self.process_value(item, table_name.clone(), Some(parent_id), file_name)?;
+        }
+
+        // Add file name column if configured and at root level
+        if self.params.add_file_name && parent_id.is_none() {
+            headers.push(FILE_NAME_COLUMN.to_string());
+            row.insert(FILE_NAME_COLUMN.to_string(), self.file_name.to_string());
+        }
+        if parent_id.is_none() {
+            for (column, value) in self.carried {
+                headers.push(column.clone());
+                types.remove(column);
+                row.insert(column.clone(), value.clone());
+            }
+        }
+
+        self.add_row(table, headers, row, types, parent_id)
+    }
+
+    /// Expands nested objects into `<key><separator><child>` fields, yielding the
+    /// column name, the `.` separated JSON path and the value of each field.
+    ///
+    /// Objects deeper than `flatten.max_depth` are kept as a single JSON column, as
+    /// are objects under keys that hold arrays elsewhere, or scalars elsewhere when
+    /// `on_type_conflict` is `stringify`.
+    fn flatten<'v>(
+        &self,
+        obj: &'v Map<String, Value>,
+        table_path: &str,
+        prefix: Option<(&str, &str)>,
+        depth: usize,
+        fields: &mut Vec<(String, String, &'v Value)>,
+    ) -> Result<()> {
+        let flatten = &self.params.flatten;
+        let within_depth = match flatten.max_depth {
+            Some(max_depth) => depth < max_depth,
+            None => true,
+        };
+        for (key, val) in obj {
+            let (column, path) = match prefix {
+                Some((column, path)) => (
+                    format!("{}{}{}", column, flatten.separator, key),
+                    format!("{}.{}", path, key),
+                ),
+                None => (key.clone(), key.clone()),
+            };
+            let full_path = format!("{}.{}", table_path, path);
+            let conflict = self.shapes.is_conflict(&full_path);
+            if conflict && self.params.on_type_conflict == TypeConflictPolicy::Error {
+                bail!(
+                    "Key '{}' holds both scalars and objects, on_type_conflict is 'error'",
+                    full_path
+                );
+            }
+
+            match val {
+                Value::Object(nested)
+                    if within_depth
+                        && !self.shapes.arrays.contains(&full_path)
+                        && !(conflict
+                            && self.params.on_type_conflict == TypeConflictPolicy::Stringify) =>
+                {
+                    self.flatten(
+                        nested,
+                        table_path,
+                        Some((&column, &path)),
+                        depth + 1,
+                        fields,
+                    )?;
                 }
+                _ => fields.push((column, path, val)),
             }
-            _ => {}
         }
         Ok(())
     }
 
-    pub fn process_table_mapping(
+    /// Emits the columns and child tables declared by the mapping. Without a mapped
+    /// primary key the row also keeps the generated key of its child rows, in a
+    /// column that `Config::validate` reserves.
+    fn walk_mapped_object(
         &mut self,
-        value: &Value,
-        mapping: &TableMapping,
-        _file_name: &str,
+        obj: &Map<String, Value>,
+        mapping: &'a IndexMap<String, MappingType>,
+        table: &TableContext<'a>,
+        parent_id: Option<&RowId>,
+        index: usize,
     ) -> Result<()> {
-        if let Value::Array(arr) = value {
-            for order in arr {
-                if let Some(order_obj) = order.as_object() {
-                    let mut row = HashMap::new();
-
-                    // Process order ID
-                    if let Some(id) = order_obj.get("id") {
-                        if let Some(MappingType::Column {
-                            mapping: col_mapping,
-                        }) = mapping.table_mapping.get("id")
-                        {
-                            row.insert(col_mapping.destination.clone(), self.format_value(id));
-                        }
-                    }
+        let mut row = HashMap::new();
+        let mut types = HashMap::new();
+        let mut headers = Vec::new();
+        let mut primary_key = Vec::new();
 
-                    // Process items
-                    if let Some(items) = order_obj.get("items") {
-                        if let Some(MappingType::Table(table_mapping)) =
-                            mapping.table_mapping.get("items")
+        for (path, mapping_type) in mapping {
+            let (column, as_json) = match mapping_type {
+                MappingType::Column { mapping } => (mapping, false),
+                MappingType::Json { mapping } => (mapping, true),
+                MappingType::Table(_) => continue,
+            };
+            // The file name and carried columns have to be mapped like any other key
+            let carried = self
+                .carried
+                .iter()
+                .find(|(carried, _)| parent_id.is_none() && carried == path);
+            // Cells of a single JSON value are typed by the value, others by their text
+            let (formatted, value_type) =
+                if self.params.add_file_name && parent_id.is_none() && path == FILE_NAME_COLUMN {
+                    (self.file_name.to_string(), None)
+                } else if let Some((_, value)) = carried {
+                    (value.clone(), None)
+                } else if as_json {
+                    let value = Self::lookup(obj, path);
+                    let value_type = value.map(|_| Some(ColumnType::String));
+                    (value.map(Value::to_string).unwrap_or_default(), value_type)
+                } else {
+                    match (Self::lookup(obj, path), &column.delimiter) {
+                        (Some(Value::Array(items)), Some(delimiter))
+                            if !items.iter().any(|item| item.is_object() || item.is_array()) =>
                         {
-                            let table_name = table_mapping.destination.clone();
-                            let new_rows: Vec<_> = items
-                                .as_array()
-                                .unwrap_or(&vec![])
+                            let items: Vec<_> = items
                                 .iter()
-                                .filter_map(|item| {
-                                    if let Some(item_obj) = item.as_object() {
-                                        let mut item_row = HashMap::new();
-
-                                        // Add order_id to item row
-                                        if let Some(order_id) = order_obj.get("id") {
-                                            item_row.insert(
-                                                "order_id".to_string(),
-                                                self.format_value(order_id),
-                                            );
-                                        }
-
-                                        // Process item fields
-                                        for (key, mapping_type) in &table_mapping.table_mapping {
-                                            if let Some(value) = item_obj.get(key) {
-                                                if let MappingType::Column {
-                                                    mapping: col_mapping,
-                                                } = mapping_type
-                                                {
-                                                    item_row.insert(
-                                                        col_mapping.destination.clone(),
-                                                        self.format_value(value),
-                                                    );
-                                                }
-                                            }
-                                        }
-
-                                        Some(item_row)
-                                    } else {
-                                        None
-                                    }
-                                })
+                                .map(|item| format_value(item, &self.params.format))
                                 .collect();
-
-                            let table =
-                                self.tables.entry(table_name.clone()).or_insert_with(|| {
-                                    TableData {
-                                        headers: vec![
-                                            "item_id".to_string(),
-                                            "quantity".to_string(),
-                                            "order_id".to_string(),
-                                        ],
-                                        rows: Vec::new(),
-                                    }
-                                });
-                            table.rows.extend(new_rows);
+                            let value_type = (!items.is_empty()).then_some(ColumnType::String);
+                            (items.join(delimiter), Some(value_type))
                         }
+                        (value, _) => (
+                            value
+                                .map(|value| format_value(value, &self.params.format))
+                                .unwrap_or_default(),
+                            value.map(ColumnType::of_value),
+                        ),
                     }
+                };
+            if let Some(value_type) = value_type {
+                types.insert(column.destination.clone(), value_type);
+            }
+            if column.primary_key {
+                let key_type = value_type.unwrap_or_else(|| ColumnType::infer(&formatted));
+                primary_key.push((formatted.clone(), key_type));
+            }
+            headers.push(column.destination.clone());
+            row.insert(column.destination.clone(), formatted);
+        }
+
+        let mut row_id = None;
+        for (path, mapping_type) in mapping {
+            let MappingType::Table(table_mapping) = mapping_type else {
+                continue;
+            };
+            // A single object is treated as a one-item array
+            let items = match Self::lookup(obj, path) {
+                Some(Value::Array(items)) => items.as_slice(),
+                Some(value @ Value::Object(_)) => std::slice::from_ref(value),
+                _ => continue,
+            };
+            let row_id = row_id
+                .get_or_insert_with(|| self.row_id(obj, table, parent_id, index, &primary_key))
+                .clone();
+            // Without a mapped primary key the row keeps the generated key, named
+            // after the array key like in inferred tables
+            if primary_key.is_empty() {
+                let column = path.replace('.', &self.params.flatten.separator);
+                headers.push(column.clone());
+                types.insert(column.clone(), row_id.column_type);
+                row.insert(column, row_id.value.clone());
+            }
+
+            let child = TableContext {
+                name: table_mapping.destination.clone(),
+                path: format!("{}.{}", table.path, path),
+                mapping: Some(&table_mapping.table_mapping),
+                parent_key: Some(
+                    table_mapping
+                        .parent_key
+                        .as_ref()
+                        .map_or(PARENT_ID_COLUMN, |key| key.destination.as_str()),
+                ),
+            };
+            for (i, item) in items.iter().enumerate() {
+                self.walk(item, &child, Some(&row_id), i)?;
+            }
+        }
+
+        self.add_row(table, headers, row, types, parent_id)
+    }
+
+    /// Resolves a mapping key against an object, following `.` separated paths.
+    fn lookup<'v>(obj: &'v Map<String, Value>, path: &str) -> Option<&'v Value> {
+        if let Some(value) = obj.get(path) {
+            return Some(value);
+        }
+
+        let mut parts = path.split('.');
+        let mut current = obj.get(parts.next()?)?;
+        for part in parts {
+            current = current.get(part)?;
+        }
+        Some(current)
+    }
+
+    /// Identifier joining the child rows of `obj` back to its own row.
+    ///
+    /// Mapped primary keys win, then the configured `parent_key_field`; otherwise
+    /// the key is hashed from the row's position and content the same way
+    /// php-jsonparser does (`<type path>_<md5>`), which keeps it stable between
+    /// runs and unique across parent rows and input files. A key of several
+    /// primary key columns, joined by `,`, and a hashed key are strings.
+    fn row_id(
+        &self,
+        obj: &Map<String, Value>,
+        table: &TableContext<'a>,
+        parent_id: Option<&RowId>,
+        index: usize,
+        primary_key: &[(String, Option<ColumnType>)],
+    ) -> RowId {
+        match primary_key {
+            [] => {}
+            [(value, column_type)] => {
+                return RowId {
+                    value: value.clone(),
+                    column_type: *column_type,
                 }
             }
+            _ => {
+                let values: Vec<&str> = primary_key.iter().map(|(v, _)| v.as_str()).collect();
+                return RowId {
+                    value: values.join(","),
+                    column_type: Some(ColumnType::String),
+                };
+            }
+        }
+        if let Some(field) = &self.params.parent_key_field {
+            if let Some(value) = obj.get(field).filter(|v| !v.is_object() && !v.is_array()) {
+                return RowId {
+                    value: format_value(value, &self.params.format),
+                    column_type: ColumnType::of_value(value),
+                };
+            }
         }
 
-        Ok(())
+        let identity = format!(
+            "{}\n{}\n{}\n{}",
+            self.source,
+            parent_id.map_or("", |id| id.value.as_str()),
+            index,
+            serde_json::to_string(obj).unwrap_or_default()
+        );
+        RowId {
+            value: format!("{}_{:x}", table.path, md5::compute(identity)),
+            column_type: Some(ColumnType::String),
+        }
     }
 
-    fn get_root_node<'a>(&self, json: &'a Value, root_node: &str) -> Result<&'a Value> {
-        if root_node.is_empty() {
-            return Ok(json);
+    fn add_row(
+        &mut self,
+        table: &TableContext<'a>,
+        mut headers: Vec<String>,
+        mut row: HashMap<String, String>,
+        mut types: HashMap<String, Option<ColumnType>>,
+        parent_id: Option<&RowId>,
+    ) -> Result<()> {
+        // Add parent ID if this is a child table, typed like the parent's key
+        if let (Some(parent_key), Some(parent_id)) = (table.parent_key, parent_id) {
+            headers.push(parent_key.to_string());
+            types.insert(parent_key.to_string(), parent_id.column_type);
+            row.insert(parent_key.to_string(), parent_id.value.clone());
         }
 
-        let mut current = json;
-        for node in root_node.split('.') {
-            current = current.get(node).ok_or_else(|| {
-                anyhow::anyhow!(
-                    "Root node path '{}' not found in JSON at node '{}'",
-                    root_node,
-                    node
-                )
-            })?;
+        let data = self
+            .tables
+            .entry(table.name.clone())
+            .or_insert_with(|| TableData {
+                headers: Vec::new(),
+                types: Vec::new(),
+                row_count: 0,
+                rows: Vec::new(),
+                writer: None,
+            });
+
+        // Update headers if needed
+        for header in headers {
+            if !data.headers.contains(&header) {
+                data.headers.push(header);
+                data.types.push(None);
+            }
+        }
+
+        // Fill in missing values with empty strings
+        let record: Vec<String> = data
+            .headers
+            .iter()
+            .map(|header| row.remove(header).unwrap_or_default())
+            .collect();
+
+        // Cells without a JSON value, like keys, are typed by their text, where the
+        // configured null value is not a value of the column type
+        let null = &self.params.format.null;
+        for ((header, column_type), value) in data.headers.iter().zip(&mut data.types).zip(&record)
+        {
+            let value_type = match types.remove(header) {
+                Some(value_type) => value_type,
+                None if value == null => None,
+                None => ColumnType::infer(value),
+            };
+            if let Some(value_type) = value_type {
+                *column_type = Some(column_type.map_or(value_type, |t| t.merge(value_type)));
+            }
         }
-        Ok(current)
+
+        data.row_count += 1;
+        if self.dry_run {
+            return Ok(());
+        }
+
+        if !self.params.streaming {
+            data.rows.push(record);
+            return Ok(());
+        }
+        let writer = match &mut data.writer {
+            Some(writer) => writer,
+            None => {
+                let output_path = self.output_dir.join(format!("{}.csv", table.name));
+                data.writer.insert(TableWriter::create(
+                    &output_path,
+                    &data.headers,
+                    &self.params.csv,
+                )?)
+            }
+        };
+        writer.write_row(&record)
     }
+}
 
-    fn format_value(&self, value: &Value) -> String {
-        match value {
-            Value::String(s) => s.clone(),
-            Value::Number(n) => n.to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::Null => String::new(),
-            _ => value.to_string(),
+/// Renders a scalar as a CSV value, other values as JSON. Numbers keep the exact
+/// text of the input thanks to serde_json's `arbitrary_precision`.
+fn format_value(value: &Value, format: &FormatOptions) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => format_number(n),
+        Value::Bool(b) => match (format.boolean, b) {
+            (BooleanFormat::Text, _) => b.to_string(),
+            (BooleanFormat::Numeric, true) => "1".to_string(),
+            (BooleanFormat::Numeric, false) => "0".to_string(),
+        },
+        Value::Null => format.null.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Writes the digits of a number as they appear in the input, with the exponent
+/// spelled `e+3` or `e-7`, as later serde_json releases do, for any input and
+/// serde_json version.
+fn format_number(n: &Number) -> String {
+    let text = n.to_string();
+    let Some(at) = text.find(['e', 'E']) else {
+        return text;
+    };
+    let (mantissa, exponent) = (&text[..at], &text[at + 1..]);
+    if exponent.starts_with(['+', '-']) {
+        format!("{}e{}", mantissa, exponent)
+    } else {
+        format!("{}e+{}", mantissa, exponent)
+    }
+}
+
+fn collect_primary_keys(
+    mapping: &IndexMap<String, MappingType>,
+    table_name: &str,
+    keys: &mut HashMap<String, HashSet<String>>,
+) {
+    for mapping_type in mapping.values() {
+        match mapping_type {
+            MappingType::Column { mapping } | MappingType::Json { mapping } => {
+                if mapping.primary_key {
+                    keys.entry(table_name.to_string())
+                        .or_default()
+                        .insert(mapping.destination.clone());
+                }
+            }
+            MappingType::Table(table_mapping) => {
+                if let Some(parent_key) = &table_mapping.parent_key {
+                    if parent_key.primary_key {
+                        keys.entry(table_mapping.destination.clone())
+                            .or_default()
+                            .insert(parent_key.destination.clone());
+                    }
+                }
+                collect_primary_keys(
+                    &table_mapping.table_mapping,
+                    &table_mapping.destination,
+                    keys,
+                );
+            }
         }
     }
+}
 
-    pub fn write_tables(&self) -> Result<()> {
-        for (table_name, data) in &self.tables {
-            let output_path = self.output_dir.join(format!("{}.csv", table_name));
-            // Ensure output directory exists
-            if let Some(parent) = output_path.parent() {
-                fs::create_dir_all(parent)?;
+/// Callback receiving the root node index and an item found at its path, and
+/// which root node paths were found.
+struct StreamState<F> {
+    on_item: F,
+    found: Vec<bool>,
+}
+
+/// Key under which serde_json hands out the text of numbers.
+const ARBITRARY_PRECISION_NUMBER: &str = "$serde_json::private::Number";
+
+/// Descends into the root node paths while deserializing, skipping subtrees no
+/// path leads into, and hands the items found at their ends to `on_item`.
+struct RootNodeSeed<'p, F> {
+    /// Remaining segments of the paths leading into this node, by root node index.
+    paths: Vec<(usize, &'p [Segment])>,
+    state: &'p mut StreamState<F>,
+}
+
+impl<'de, F> DeserializeSeed<'de> for RootNodeSeed<'_, F>
+where
+    F: FnMut(usize, Value) -> Result<()>,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match self.paths.as_slice() {
+            [(root, [])] => deserializer.deserialize_any(ItemsVisitor {
+                root: *root,
+                state: self.state,
+            }),
+            paths if paths.iter().any(|(_, rest)| rest.is_empty()) => {
+                // A root node ends at this node while others continue below it, so
+                // it is loaded as a whole and the rest is selected in memory
+                let value = Value::deserialize(deserializer)?;
+                for (root, rest) in paths {
+                    for node in select(rest, &value) {
+                        self.state.found[*root] = true;
+                        let items = match node {
+                            Value::Array(items) => items.iter().collect(),
+                            _ => vec![node],
+                        };
+                        for item in items {
+                            (self.state.on_item)(*root, item.clone()).map_err(de::Error::custom)?;
+                        }
+                    }
+                }
+                Ok(())
             }
+            _ => deserializer.deserialize_any(RootNodeVisitor {
+                paths: self.paths,
+                state: self.state,
+            }),
+        }
+    }
+}
 
-            // Write headers without quotes
-            let mut file = fs::File::create(&output_path)?;
-            writeln!(file, "{}", data.headers.join(","))?;
+/// Matches the next segment of each path against the keys of an object or the
+/// items of an array. Scalars never match.
+struct RootNodeVisitor<'p, F> {
+    paths: Vec<(usize, &'p [Segment])>,
+    state: &'p mut StreamState<F>,
+}
 
-            // Write rows with quotes
-            let mut writer = csv::WriterBuilder::new()
-                .quote_style(csv::QuoteStyle::Always)
-                .has_headers(false)
-                .from_writer(file);
+impl<'p, F> RootNodeVisitor<'p, F> {
+    /// Paths continuing into the child for which `matches` accepts the next segment.
+    fn child_paths(&self, matches: impl Fn(&Segment) -> bool) -> Vec<(usize, &'p [Segment])> {
+        self.paths
+            .iter()
+            .filter_map(|&(root, path)| match path.split_first() {
+                Some((segment, rest)) if matches(segment) => Some((root, rest)),
+                _ => None,
+            })
+            .collect()
+    }
+}
 
-            for row in &data.rows {
-                let record: Vec<_> = data
-                    .headers
-                    .iter()
-                    .map(|header| row.get(header).map(String::as_str).unwrap_or(""))
-                    .collect();
-                writer.write_record(&record)?;
+impl<'de, F> Visitor<'de> for RootNodeVisitor<'_, F>
+where
+    F: FnMut(usize, Value) -> Result<()>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object or an array")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        while let Some(key) = map.next_key::<String>()? {
+            // With `arbitrary_precision` numbers are maps with this single key
+            if key == ARBITRARY_PRECISION_NUMBER {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            let paths = self.child_paths(|segment| match segment {
+                Segment::Key(node) => key == *node,
+                Segment::Wildcard => true,
+                Segment::Index(_) => false,
+            });
+            if paths.is_empty() {
+                map.next_value::<IgnoredAny>()?;
+            } else {
+                map.next_value_seed(RootNodeSeed {
+                    paths,
+                    state: &mut *self.state,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut index = 0;
+        loop {
+            let paths = self.child_paths(|segment| match segment {
+                Segment::Index(i) => index == *i,
+                Segment::Wildcard => true,
+                Segment::Key(_) => false,
+            });
+            let next = if paths.is_empty() {
+                seq.next_element::<IgnoredAny>()?.map(drop)
+            } else {
+                seq.next_element_seed(RootNodeSeed {
+                    paths,
+                    state: &mut *self.state,
+                })?
+            };
+            if next.is_none() {
+                return Ok(());
             }
+            index += 1;
+        }
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
 
-            writer.flush()?;
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Streams the items of the selected array, any other value is a single item.
+struct ItemsVisitor<'p, F> {
+    root: usize,
+    state: &'p mut StreamState<F>,
+}
+
+impl<F> ItemsVisitor<'_, F>
+where
+    F: FnMut(usize, Value) -> Result<()>,
+{
+    fn emit<E: de::Error>(self, value: Value) -> Result<(), E> {
+        self.state.found[self.root] = true;
+        (self.state.on_item)(self.root, value).map_err(E::custom)
+    }
+}
+
+impl<'de, F> Visitor<'de> for ItemsVisitor<'_, F>
+where
+    F: FnMut(usize, Value) -> Result<()>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.state.found[self.root] = true;
+        while let Some(item) = seq.next_element::<Value>()? {
+            (self.state.on_item)(self.root, item).map_err(de::Error::custom)?;
         }
         Ok(())
     }
+
+    fn visit_map<A>(self, map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let value = Value::deserialize(de::value::MapAccessDeserializer::new(map))?;
+        self.emit(value)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<(), E> {
+        self.emit(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<(), E> {
+        self.emit(Value::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<(), E> {
+        self.emit(Value::from(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<(), E> {
+        self.emit(Value::from(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<(), E> {
+        self.emit(Value::String(v.to_string()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        self.emit(Value::Null)
+    }
 }
@@ -0,0 +1,278 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Type of a column inferred from the values written to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Boolean,
+    Integer,
+    Numeric,
+    /// `YYYY-MM-DD`
+    Date,
+    /// ISO 8601 date and time, e.g. `2024-01-31T12:00:00Z`
+    Timestamp,
+    String,
+    /// Values of several incompatible types.
+    Mixed,
+}
+
+impl ColumnType {
+    /// Type of a single CSV value, `None` for empty values which fit any type.
+    pub fn infer(value: &str) -> Option<Self> {
+        if value.is_empty() {
+            return None;
+        }
+        Some(match value {
+            "true" | "false" => Self::Boolean,
+            _ if is_integer(value) => Self::Integer,
+            _ if is_number(value) => Self::Numeric,
+            _ if is_date(value) => Self::Date,
+            _ if is_timestamp(value) => Self::Timestamp,
+            _ => Self::String,
+        })
+    }
+
+    /// Type of a JSON value, `None` for `null` and empty strings which fit any type.
+    /// Strings are only dates and timestamps by their content, a string holding
+    /// `"100"` or `"true"` stays a string.
+    pub fn of_value(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::Null => return None,
+            Value::String(s) if s.is_empty() => return None,
+            Value::Bool(_) => Self::Boolean,
+            Value::Number(n) if is_integer(&n.to_string()) => Self::Integer,
+            Value::Number(_) => Self::Numeric,
+            Value::String(s) if is_date(s) => Self::Date,
+            Value::String(s) if is_timestamp(s) => Self::Timestamp,
+            Value::String(_) | Value::Array(_) | Value::Object(_) => Self::String,
+        })
+    }
+
+    /// Narrowest type holding the values of both types.
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Integer, Self::Numeric) | (Self::Numeric, Self::Integer) => Self::Numeric,
+            (Self::Date, Self::Timestamp) | (Self::Timestamp, Self::Date) => Self::Timestamp,
+            _ => Self::Mixed,
+        }
+    }
+
+    /// Keboola Storage base type, the `KBC.datatype.basetype` column metadata.
+    pub fn basetype(self) -> &'static str {
+        match self {
+            Self::Boolean => "BOOLEAN",
+            Self::Integer => "INTEGER",
+            Self::Numeric => "NUMERIC",
+            Self::Date => "DATE",
+            Self::Timestamp => "TIMESTAMP",
+            Self::String | Self::Mixed => "STRING",
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::Numeric => "numeric",
+            Self::Date => "date",
+            Self::Timestamp => "timestamp",
+            Self::String => "string",
+            Self::Mixed => "mixed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// `-?(0|[1-9][0-9]*)`, integers with leading zeros are usually identifiers.
+fn is_integer(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    match digits.as_bytes() {
+        [] => false,
+        [b'0'] => true,
+        [b'0', ..] => false,
+        bytes => bytes.iter().all(u8::is_ascii_digit),
+    }
+}
+
+/// JSON number syntax, `-?int(.digits)?([eE][+-]?digits)?`.
+fn is_number(value: &str) -> bool {
+    let value = value.strip_prefix('-').unwrap_or(value);
+    let (mantissa, exponent) = match value.find(['e', 'E']) {
+        Some(i) => (&value[..i], Some(&value[i + 1..])),
+        None => (value, None),
+    };
+    let (int, fraction) = match mantissa.split_once('.') {
+        Some((int, fraction)) => (int, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    is_integer(int)
+        && fraction.map_or(true, all_digits)
+        && exponent.map_or(true, |e| {
+            all_digits(e.strip_prefix(['+', '-']).unwrap_or(e))
+        })
+}
+
+/// `YYYY-MM-DD` with a valid month and day of month.
+fn is_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let is_digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+    if bytes.len() != 10
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !is_digits(0..4)
+        || !is_digits(5..7)
+        || !is_digits(8..10)
+    {
+        return false;
+    }
+    let month: u8 = value[5..7].parse().unwrap_or(0);
+    let day: u8 = value[8..10].parse().unwrap_or(0);
+    (1..=12).contains(&month) && (1..=31).contains(&day)
+}
+
+/// A date followed by `T` or a space, `HH:MM:SS`, optional fractional seconds and
+/// an optional `Z` or `+HH:MM` offset.
+fn is_timestamp(value: &str) -> bool {
+    if value.len() < 19 || !value.is_char_boundary(10) || !value.is_char_boundary(19) {
+        return false;
+    }
+    let (date, rest) = value.split_at(10);
+    let (time, mut zone) = rest.split_at(9);
+    let time = time.as_bytes();
+    let two_digits = |b: &[u8]| b.iter().all(u8::is_ascii_digit);
+    let valid_time = matches!(time[0], b'T' | b' ')
+        && two_digits(&time[1..3])
+        && time[3] == b':'
+        && two_digits(&time[4..6])
+        && time[6] == b':'
+        && two_digits(&time[7..9]);
+    if !is_date(date) || !valid_time {
+        return false;
+    }
+
+    if let Some(fraction) = zone.strip_prefix('.') {
+        let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return false;
+        }
+        zone = &fraction[digits..];
+    }
+    match zone.as_bytes() {
+        [] | [b'Z'] => true,
+        [b'+' | b'-', h1, h2, b':', m1, m2] => two_digits(&[*h1, *h2, *m1, *m2]),
+        _ => false,
+    }
+}
+
+/// Inferred schemas of the output tables, persisted in the component state so that
+/// the next run can detect schema drift.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    #[serde(default)]
+    pub tables: BTreeMap<String, TableSchema>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TableSchema {
+    /// Columns in header order, with `null` for columns that only held empty values.
+    pub columns: IndexMap<String, Option<ColumnType>>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SchemaChange {
+    TableAdded(String),
+    TableRemoved(String),
+    ColumnAdded {
+        table: String,
+        column: String,
+    },
+    ColumnRemoved {
+        table: String,
+        column: String,
+    },
+    TypeChanged {
+        table: String,
+        column: String,
+        from: ColumnType,
+        to: ColumnType,
+    },
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TableAdded(table) => write!(f, "table '{}' was added", table),
+            Self::TableRemoved(table) => write!(f, "table '{}' was removed", table),
+            Self::ColumnAdded { table, column } => {
+                write!(f, "column '{}' was added to table '{}'", column, table)
+            }
+            Self::ColumnRemoved { table, column } => {
+                write!(f, "column '{}' was removed from table '{}'", column, table)
+            }
+            Self::TypeChanged {
+                table,
+                column,
+                from,
+                to,
+            } => write!(
+                f,
+                "column '{}' of table '{}' changed type from {} to {}",
+                column, table, from, to
+            ),
+        }
+    }
+}
+
+impl State {
+    /// Changes from the `previous` run's schemas to these. Columns that only held
+    /// empty values in either run are not compared by type.
+    pub fn changes_since(&self, previous: &State) -> Vec<SchemaChange> {
+        let mut changes = Vec::new();
+        for (table, schema) in &self.tables {
+            let Some(previous_schema) = previous.tables.get(table) else {
+                changes.push(SchemaChange::TableAdded(table.clone()));
+                continue;
+            };
+            for (column, column_type) in &schema.columns {
+                match previous_schema.columns.get(column) {
+                    None => changes.push(SchemaChange::ColumnAdded {
+                        table: table.clone(),
+                        column: column.clone(),
+                    }),
+                    Some(&Some(from)) => match column_type {
+                        Some(to) if *to != from => changes.push(SchemaChange::TypeChanged {
+                            table: table.clone(),
+                            column: column.clone(),
+                            from,
+                            to: *to,
+                        }),
+                        _ => {}
+                    },
+                    Some(None) => {}
+                }
+            }
+            for column in previous_schema.columns.keys() {
+                if !schema.columns.contains_key(column) {
+                    changes.push(SchemaChange::ColumnRemoved {
+                        table: table.clone(),
+                        column: column.clone(),
+                    });
+                }
+            }
+        }
+        for table in previous.tables.keys() {
+            if !self.tables.contains_key(table) {
+                changes.push(SchemaChange::TableRemoved(table.clone()));
+            }
+        }
+        changes
+    }
+}
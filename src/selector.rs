@@ -0,0 +1,189 @@
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Path to the node(s) holding the root records, as configured in `root_node`.
+///
+/// Keys are separated by `.`, e.g. `data.records`. Keys containing dots or
+/// brackets are quoted, `data["user.name"]`, or escaped with a backslash,
+/// `data.user\.name`. `[0]` selects an array item, and `*` or `[*]` fans out
+/// over all items of an array or all values of an object; the arrays found
+/// through a wildcard are concatenated into the root table, e.g.
+/// `pages[*].records`. A leading `$` is allowed and ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Selector {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SelectorError {
+    #[error("empty key at position {0}")]
+    EmptyKey(usize),
+    #[error("unterminated '[' at position {0}")]
+    UnterminatedBracket(usize),
+    #[error("unterminated quote at position {0}")]
+    UnterminatedQuote(usize),
+    #[error("invalid array index '{0}'")]
+    InvalidIndex(String),
+    #[error("unexpected '{1}' at position {0}")]
+    UnexpectedChar(usize, char),
+}
+
+impl Selector {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Whether the selector can match any number of nodes, including none.
+    pub fn has_wildcard(&self) -> bool {
+        self.segments.contains(&Segment::Wildcard)
+    }
+
+    /// Nodes matched by the selector, in document order.
+    pub fn select<'v>(&self, value: &'v Value) -> Vec<&'v Value> {
+        select(&self.segments, value)
+    }
+}
+
+/// Nodes reached from `value` by following `path`, in document order.
+pub fn select<'v>(path: &[Segment], value: &'v Value) -> Vec<&'v Value> {
+    let mut matches = vec![value];
+    for segment in path {
+        matches = matches
+            .into_iter()
+            .flat_map(|node| segment.children(node))
+            .collect();
+    }
+    matches
+}
+
+impl Segment {
+    fn children<'v>(&self, node: &'v Value) -> Vec<&'v Value> {
+        match (self, node) {
+            (Segment::Key(key), Value::Object(obj)) => obj.get(key).into_iter().collect(),
+            (Segment::Index(index), Value::Array(items)) => items.get(*index).into_iter().collect(),
+            (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+            (Segment::Wildcard, Value::Object(obj)) => obj.values().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl FromStr for Selector {
+    type Err = SelectorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = s.chars().collect();
+        let mut segments = Vec::new();
+        let mut pos = 0;
+
+        if chars.first() == Some(&'$') {
+            pos = match chars.get(1) {
+                Some('.') => 2,
+                _ => 1,
+            };
+        }
+
+        while pos < chars.len() {
+            if chars[pos] == '[' {
+                let (segment, end) = parse_bracket(&chars, pos)?;
+                segments.push(segment);
+                pos = end;
+            } else {
+                let start = pos;
+                let mut key = String::new();
+                while pos < chars.len() && chars[pos] != '.' && chars[pos] != '[' {
+                    if chars[pos] == '\\' && pos + 1 < chars.len() {
+                        pos += 1;
+                    }
+                    key.push(chars[pos]);
+                    pos += 1;
+                }
+                if key.is_empty() {
+                    return Err(SelectorError::EmptyKey(start));
+                }
+                let escaped = chars[start] == '\\';
+                segments.push(if key == "*" && !escaped {
+                    Segment::Wildcard
+                } else {
+                    Segment::Key(key)
+                });
+            }
+
+            // Segments are followed by `.`, `[` or the end of the selector
+            match chars.get(pos) {
+                None | Some('[') => {}
+                Some('.') if pos + 1 < chars.len() => pos += 1,
+                Some('.') => return Err(SelectorError::EmptyKey(pos + 1)),
+                Some(&c) => return Err(SelectorError::UnexpectedChar(pos, c)),
+            }
+        }
+
+        Ok(Self {
+            raw: s.to_string(),
+            segments,
+        })
+    }
+}
+
+/// Parses `[0]`, `[*]` or `["key"]` starting at `start`, returning the position after `]`.
+fn parse_bracket(chars: &[char], start: usize) -> Result<(Segment, usize), SelectorError> {
+    let mut pos = start + 1;
+    match chars.get(pos) {
+        Some(&quote @ ('"' | '\'')) => {
+            let mut key = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None => return Err(SelectorError::UnterminatedQuote(start + 1)),
+                    Some('\\') if pos + 1 < chars.len() => {
+                        key.push(chars[pos + 1]);
+                        pos += 2;
+                    }
+                    Some(&c) if c == quote => break,
+                    Some(&c) => {
+                        key.push(c);
+                        pos += 1;
+                    }
+                }
+            }
+            match chars.get(pos + 1) {
+                Some(']') => Ok((Segment::Key(key), pos + 2)),
+                Some(&c) => Err(SelectorError::UnexpectedChar(pos + 1, c)),
+                None => Err(SelectorError::UnterminatedBracket(start)),
+            }
+        }
+        _ => {
+            let end = chars[pos..]
+                .iter()
+                .position(|&c| c == ']')
+                .map(|offset| pos + offset)
+                .ok_or(SelectorError::UnterminatedBracket(start))?;
+            let inner: String = chars[pos..end].iter().collect();
+            let segment = match inner.trim() {
+                "*" => Segment::Wildcard,
+                index => Segment::Index(
+                    index
+                        .parse()
+                        .map_err(|_| SelectorError::InvalidIndex(inner.clone()))?,
+                ),
+            };
+            Ok((segment, end + 1))
+        }
+    }
+}
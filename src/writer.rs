@@ -0,0 +1,358 @@
+use crate::config::{
+    CompressionFormat, CompressionOptions, CsvOptions, LineTerminator, QuoteStyle, SlicingOptions,
+};
+use anyhow::{Context, Result};
+use csv::StringRecord;
+use flate2::write::GzEncoder;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes the rows of one output table to `<table>.csv`.
+///
+/// Rows may be written before the table schema is complete. Columns are only ever
+/// appended, so when the schema grows after the header line was written,
+/// [`TableWriter::finish`] rewrites the file once with the final header and pads
+/// the earlier rows.
+pub struct TableWriter {
+    path: PathBuf,
+    writer: csv::Writer<File>,
+    dialect: CsvOptions,
+    /// Number of columns in the header line already written to the file.
+    written_columns: usize,
+}
+
+impl TableWriter {
+    pub fn create(path: &Path, headers: &[String], dialect: &CsvOptions) -> Result<Self> {
+        // Ensure output directory exists
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = File::create(path)?;
+        if dialect.bom {
+            file.write_all("\u{feff}".as_bytes())?;
+        }
+
+        // Column names are only quoted when needed unless `quote_header` is set
+        let header_style = if dialect.quote_header {
+            dialect.quote_style
+        } else {
+            QuoteStyle::Necessary
+        };
+        let mut header_writer = writer_builder(dialect, header_style)?.from_writer(&mut file);
+        header_writer.write_record(headers)?;
+        header_writer.flush()?;
+        drop(header_writer);
+
+        let writer = writer_builder(dialect, dialect.quote_style)?.from_writer(file);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            dialect: dialect.clone(),
+            written_columns: headers.len(),
+        })
+    }
+
+    pub fn write_row<I, T>(&mut self, record: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        self.writer.write_record(record)?;
+        Ok(())
+    }
+
+    /// Flushes the pending rows and returns the current end of the file.
+    pub fn checkpoint(&mut self) -> Result<u64> {
+        self.writer.flush()?;
+        Ok((&mut self.writer.get_ref()).stream_position()?)
+    }
+
+    /// Drops the rows written after `position` was returned by [`TableWriter::checkpoint`].
+    pub fn rollback(&mut self, position: u64) -> Result<()> {
+        self.writer.flush()?;
+        let mut file = self.writer.get_ref();
+        file.set_len(position)?;
+        file.seek(SeekFrom::Start(position))?;
+        Ok(())
+    }
+
+    /// Removes the table file without finishing it.
+    pub fn discard(self) -> Result<()> {
+        drop(self.writer);
+        fs::remove_file(self.path)?;
+        Ok(())
+    }
+
+    /// Flushes the table and fixes up the header when columns were added since it was
+    /// written. The complete CSV file is then replaced by its compressed copy or split
+    /// into slices.
+    pub fn finish(
+        mut self,
+        headers: &[String],
+        compression: &CompressionOptions,
+        slicing: &SlicingOptions,
+    ) -> Result<()> {
+        self.writer.flush()?;
+        drop(self.writer);
+
+        // Slices have no header, their rows are padded while being split
+        if slicing.is_enabled() {
+            return slice(
+                &self.path,
+                headers.len(),
+                &self.dialect,
+                compression,
+                slicing,
+            );
+        }
+        if headers.len() != self.written_columns {
+            Self::rewrite(&self.path, headers, &self.dialect)?;
+        }
+        compress(&self.path, compression)
+    }
+
+    /// Rewrites the file with a new header, padding the rows to its length.
+    fn rewrite(path: &Path, headers: &[String], dialect: &CsvOptions) -> Result<()> {
+        let partial_path = path.with_extension("csv.partial");
+        fs::rename(path, &partial_path)?;
+
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(true)
+            .flexible(true)
+            .delimiter(ascii(dialect.delimiter)?)
+            .quote(ascii(dialect.quote)?)
+            .from_path(&partial_path)?;
+        let mut writer = Self::create(path, headers, dialect)?;
+        let mut record = StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let padding = headers.len().saturating_sub(record.len());
+            writer.write_row(record.iter().chain(std::iter::repeat("").take(padding)))?;
+        }
+        writer.writer.flush()?;
+
+        fs::remove_file(partial_path)?;
+        Ok(())
+    }
+}
+
+/// Replaces the file with its compressed copy, named with the extension of the format.
+fn compress(path: &Path, compression: &CompressionOptions) -> Result<()> {
+    if compression.format == CompressionFormat::None {
+        return Ok(());
+    }
+    let mut compressed_path = path.as_os_str().to_owned();
+    compressed_path.push(compression.format.extension());
+
+    let mut output = Output::create(Path::new(&compressed_path), compression)?;
+    io::copy(&mut File::open(path)?, &mut output)?;
+    output.finish()?;
+
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Replaces the file with a `<file><compression extension>` directory of slices
+/// holding its rows, padded to `columns` values.
+fn slice(
+    path: &Path,
+    columns: usize,
+    dialect: &CsvOptions,
+    compression: &CompressionOptions,
+    slicing: &SlicingOptions,
+) -> Result<()> {
+    let partial_path = path.with_extension("csv.partial");
+    fs::rename(path, &partial_path)?;
+
+    let mut dir = path.as_os_str().to_owned();
+    dir.push(compression.format.extension());
+    let dir = PathBuf::from(dir);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .delimiter(ascii(dialect.delimiter)?)
+        .quote(ascii(dialect.quote)?)
+        .from_path(&partial_path)?;
+    let mut slices = SliceWriter {
+        dir,
+        dialect,
+        compression,
+        slicing,
+        current: None,
+        count: 0,
+    };
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let padding = columns.saturating_sub(record.len());
+        slices.write_row(record.iter().chain(std::iter::repeat("").take(padding)))?;
+    }
+    slices.finish()?;
+
+    fs::remove_file(partial_path)?;
+    Ok(())
+}
+
+/// Writes rows into numbered slices, starting a new one once the current slice
+/// reached `max_rows` rows or `max_bytes` bytes.
+struct SliceWriter<'a> {
+    dir: PathBuf,
+    dialect: &'a CsvOptions,
+    compression: &'a CompressionOptions,
+    slicing: &'a SlicingOptions,
+    current: Option<Slice>,
+    /// Slices started so far.
+    count: usize,
+}
+
+struct Slice {
+    writer: csv::Writer<Counted<Output>>,
+    rows: usize,
+}
+
+impl SliceWriter<'_> {
+    fn write_row<I, T>(&mut self, record: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let full = self.current.as_ref().map_or(true, |slice| {
+            self.slicing.max_rows.is_some_and(|max| slice.rows >= max)
+                || self
+                    .slicing
+                    .max_bytes
+                    .is_some_and(|max| slice.writer.get_ref().bytes >= max)
+        });
+        if full {
+            self.finish()?;
+            self.count += 1;
+            let name = format!(
+                "part{:04}.csv{}",
+                self.count,
+                self.compression.format.extension()
+            );
+            let output = Output::create(&self.dir.join(name), self.compression)?;
+            let writer =
+                writer_builder(self.dialect, self.dialect.quote_style)?.from_writer(Counted {
+                    inner: output,
+                    bytes: 0,
+                });
+            self.current = Some(Slice { writer, rows: 0 });
+        }
+
+        let slice = self.current.as_mut().expect("a slice was started above");
+        slice.writer.write_record(record)?;
+        // Moves the row into the counted output to measure the slice
+        slice.writer.flush()?;
+        slice.rows += 1;
+        Ok(())
+    }
+
+    /// Completes the current slice.
+    fn finish(&mut self) -> Result<()> {
+        if let Some(slice) = self.current.take() {
+            let counted = slice
+                .writer
+                .into_inner()
+                .map_err(|e| anyhow::anyhow!("Failed to write slice: {}", e.error()))?;
+            counted.inner.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the bytes written through it. Flushing is left to the owner of the inner
+/// writer, so that measuring every row does not flush the file.
+struct Counted<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Output file, compressed as configured.
+enum Output {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Output {
+    fn create(path: &Path, compression: &CompressionOptions) -> Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match compression.format {
+            CompressionFormat::None => Self::Plain(file),
+            CompressionFormat::Gzip => Self::Gzip(GzEncoder::new(
+                file,
+                flate2::Compression::new(compression.level),
+            )),
+        })
+    }
+
+    fn finish(self) -> Result<()> {
+        let mut file = match self {
+            Self::Plain(file) => file,
+            Self::Gzip(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+fn writer_builder(dialect: &CsvOptions, quote_style: QuoteStyle) -> Result<csv::WriterBuilder> {
+    let mut builder = csv::WriterBuilder::new();
+    builder
+        .delimiter(ascii(dialect.delimiter)?)
+        .quote(ascii(dialect.quote)?)
+        .quote_style(match quote_style {
+            QuoteStyle::Always => csv::QuoteStyle::Always,
+            QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+            QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+            QuoteStyle::Never => csv::QuoteStyle::Never,
+        })
+        .terminator(match dialect.line_terminator {
+            LineTerminator::Lf => csv::Terminator::Any(b'\n'),
+            LineTerminator::Crlf => csv::Terminator::CRLF,
+        })
+        .has_headers(false)
+        .flexible(true);
+    Ok(builder)
+}
+
+fn ascii(c: char) -> Result<u8> {
+    u8::try_from(c)
+        .ok()
+        .filter(u8::is_ascii)
+        .with_context(|| format!("'{}' is not an ASCII character", c))
+}
@@ -0,0 +1,317 @@
+use anyhow::Result;
+use serde_json::json;
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn processor() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_processor"))
+}
+
+fn setup_data_dir() -> Result<TempDir> {
+    let data_dir = TempDir::new()?;
+    fs::write(
+        data_dir.path().join("config.json"),
+        json!({"parameters": {"in_type": "files"}}).to_string(),
+    )?;
+    fs::create_dir_all(data_dir.path().join("in/files"))?;
+    fs::write(
+        data_dir.path().join("in/files/sample.json"),
+        json!({"id": 1, "items": [{"sku": "A"}, {"sku": "B"}]}).to_string(),
+    )?;
+    Ok(data_dir)
+}
+
+#[test]
+fn test_data_dir_argument() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(output.status.success());
+
+    let root_csv = fs::read_to_string(data_dir.path().join("out/tables/root.csv"))?;
+    assert!(root_csv.starts_with("id,items\n"));
+    assert!(data_dir.path().join("out/tables/root_items.csv").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_config_input_and_output_overrides() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+    let other_dir = TempDir::new()?;
+    fs::rename(
+        data_dir.path().join("config.json"),
+        other_dir.path().join("custom.json"),
+    )?;
+    fs::rename(
+        data_dir.path().join("in/files"),
+        other_dir.path().join("input"),
+    )?;
+
+    let output = processor()
+        .arg("--config")
+        .arg(other_dir.path().join("custom.json"))
+        .arg("--input")
+        .arg(other_dir.path().join("input"))
+        .arg("--output")
+        .arg(other_dir.path().join("output"))
+        .output()?;
+    assert!(output.status.success());
+
+    assert!(other_dir.path().join("output/root.csv").exists());
+    assert!(other_dir.path().join("state.json").exists());
+    assert!(!data_dir.path().join("out").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_dry_run_prints_schema_without_writing() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .arg("--dry-run")
+        .output()?;
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("Table root_items (2 rows)\n  sku\n  JSON_parentId\n"));
+    assert!(stdout.contains("Table root (1 rows)\n  id\n  items\n"));
+    assert!(!data_dir.path().join("out").exists());
+
+    Ok(())
+}
+
+fn setup_data_dir_with_broken_file(on_error: &str) -> Result<TempDir> {
+    let data_dir = setup_data_dir()?;
+    fs::write(
+        data_dir.path().join("config.json"),
+        json!({"parameters": {"in_type": "files", "on_error": on_error}}).to_string(),
+    )?;
+    fs::create_dir_all(data_dir.path().join("in/files/nested"))?;
+    fs::write(
+        data_dir.path().join("in/files/nested/broken.json"),
+        r#"{"id": "2", "items": [{"sku": "C"}"#,
+    )?;
+    Ok(data_dir)
+}
+
+#[test]
+fn test_on_error_fail() -> Result<()> {
+    let data_dir = setup_data_dir_with_broken_file("fail")?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(!output.status.success());
+    assert!(!data_dir.path().join("out/tables/root.csv").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_on_error_skip() -> Result<()> {
+    let data_dir = setup_data_dir_with_broken_file("skip")?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains("Processed 1 files, 1 failed"));
+    let root_csv = fs::read_to_string(data_dir.path().join("out/tables/root.csv"))?;
+    assert_eq!(root_csv.lines().count(), 2);
+    assert!(!data_dir.path().join("out/files/failed").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_on_error_quarantine() -> Result<()> {
+    let data_dir = setup_data_dir_with_broken_file("quarantine")?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(output.status.success());
+
+    let failed_dir = data_dir.path().join("out/files/failed/nested");
+    assert!(failed_dir.join("broken.json").exists());
+    let report: serde_json::Value = serde_json::from_str(&fs::read_to_string(
+        failed_dir.join("broken.json.error.json"),
+    )?)?;
+    assert!(report["error"].as_str().unwrap().contains("EOF"));
+
+    Ok(())
+}
+
+#[test]
+fn test_state_and_failed_files_follow_input_and_output_overrides() -> Result<()> {
+    let data_dir = setup_data_dir_with_broken_file("quarantine")?;
+    let other_dir = TempDir::new()?;
+    fs::create_dir_all(other_dir.path().join("in"))?;
+    fs::rename(
+        data_dir.path().join("in/files"),
+        other_dir.path().join("in/files"),
+    )?;
+    fs::write(
+        other_dir.path().join("in/state.json"),
+        json!({"tables": {"root": {"columns": {"id": "integer"}}}}).to_string(),
+    )?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .arg("--input")
+        .arg(other_dir.path().join("in/files"))
+        .arg("--output")
+        .arg(other_dir.path().join("out/tables"))
+        .output()?;
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("column 'items' was added to table 'root'"));
+    assert!(other_dir.path().join("out/state.json").exists());
+    assert!(other_dir
+        .path()
+        .join("out/files/failed/nested/broken.json")
+        .exists());
+    assert!(!data_dir.path().join("out").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_state_records_schema() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(output.status.success());
+
+    let state: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(data_dir.path().join("out/state.json"))?)?;
+    assert_eq!(
+        state["tables"]["root"]["columns"],
+        json!({"id": "integer", "items": "string"})
+    );
+    assert_eq!(
+        state["tables"]["root_items"]["columns"],
+        json!({"sku": "string", "JSON_parentId": "string"})
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_drift_is_reported() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+    fs::write(
+        data_dir.path().join("in/state.json"),
+        json!({"tables": {
+            "root": {"columns": {"id": "string", "name": "string", "items": "string"}},
+            "root_items": {"columns": {"sku": "string", "JSON_parentId": "string"}}
+        }})
+        .to_string(),
+    )?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("column 'id' of table 'root' changed type from string to integer"));
+    assert!(stderr.contains("column 'name' was removed from table 'root'"));
+    assert!(!stderr.contains("table 'root_items'"));
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_drift_fail() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+    fs::write(
+        data_dir.path().join("config.json"),
+        json!({"parameters": {"in_type": "files", "schema_drift": "fail"}}).to_string(),
+    )?;
+    fs::write(
+        data_dir.path().join("in/state.json"),
+        json!({"tables": {"root": {"columns": {"id": "integer"}}}}).to_string(),
+    )?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("column 'items' was added to table 'root'"));
+    assert!(stderr.contains("table 'root_items' was added"));
+    assert!(!data_dir.path().join("out/tables/root.csv").exists());
+    assert!(!data_dir.path().join("out/state.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_drift_fail_on_first_run() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+    fs::write(
+        data_dir.path().join("config.json"),
+        json!({"parameters": {"in_type": "files", "schema_drift": "fail"}}).to_string(),
+    )?;
+    // Keboola passes an empty state before the first run
+    fs::write(data_dir.path().join("in/state.json"), "{}")?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(output.status.success());
+    assert!(data_dir.path().join("out/tables/root.csv").exists());
+    assert!(data_dir.path().join("out/state.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_schema_drift_fail_while_streaming() -> Result<()> {
+    let data_dir = setup_data_dir()?;
+    fs::write(
+        data_dir.path().join("config.json"),
+        json!({"parameters": {"in_type": "files", "streaming": true, "schema_drift": "fail"}})
+            .to_string(),
+    )?;
+    fs::write(
+        data_dir.path().join("in/state.json"),
+        json!({"tables": {"root": {"columns": {"id": "integer"}}}}).to_string(),
+    )?;
+
+    let output = processor()
+        .arg("--data-dir")
+        .arg(data_dir.path())
+        .output()?;
+    assert!(!output.status.success());
+
+    // The rows written while streaming are removed
+    assert!(!data_dir.path().join("out/tables/root.csv").exists());
+    assert!(!data_dir.path().join("out/tables/root_items.csv").exists());
+    assert!(!data_dir.path().join("out/state.json").exists());
+
+    Ok(())
+}
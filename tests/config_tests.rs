@@ -0,0 +1,302 @@
+use json2csv_processor::config::{Config, ConfigError, CsvInput, InvalidConfigReason};
+use serde_json::json;
+
+fn config_with_mapping(mapping: serde_json::Value) -> Config {
+    serde_json::from_value(json!({
+        "parameters": {
+            "in_type": "files",
+            "mapping": mapping
+        }
+    }))
+    .unwrap()
+}
+
+fn assert_invalid(config: &Config, expected_path: &str, expected_reason: InvalidConfigReason) {
+    match config.validate() {
+        Err(ConfigError::InvalidConfig { path, reason }) => {
+            assert_eq!(path, expected_path);
+            assert_eq!(reason, expected_reason);
+        }
+        other => panic!("Expected invalid configuration, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_valid_mapping() {
+    let config = config_with_mapping(json!({
+        "id": {"type": "column", "mapping": {"destination": "order_id", "primary_key": true}},
+        "order-item": {
+            "type": "table",
+            "destination": "order-items",
+            "parent_key": {"destination": "order_id", "primary_key": true},
+            "tableMapping": {
+                "price.txt_content_": {"type": "column", "mapping": {"destination": "price_value"}}
+            }
+        }
+    }));
+
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn test_duplicate_table_destination() {
+    let config = config_with_mapping(json!({
+        "items": {
+            "type": "table",
+            "destination": "order_items",
+            "tableMapping": {
+                "parts": {"type": "table", "destination": "order_items", "tableMapping": {}}
+            }
+        }
+    }));
+
+    assert_invalid(
+        &config,
+        "parameters.mapping.items.tableMapping.parts.destination",
+        InvalidConfigReason::DuplicateTable("order_items".to_string()),
+    );
+}
+
+#[test]
+fn test_duplicate_column_destination() {
+    let config = config_with_mapping(json!({
+        "items": {
+            "type": "table",
+            "destination": "order_items",
+            "parent_key": {"destination": "id"},
+            "tableMapping": {
+                "price.value": {"type": "column", "mapping": {"destination": "id"}}
+            }
+        }
+    }));
+
+    assert_invalid(
+        &config,
+        "parameters.mapping.items.parent_key.destination",
+        InvalidConfigReason::DuplicateColumn("id".to_string()),
+    );
+}
+
+#[test]
+fn test_invalid_names() {
+    let config = config_with_mapping(json!({
+        "price.value": {"type": "column", "mapping": {"destination": "price value"}}
+    }));
+    assert_invalid(
+        &config,
+        "parameters.mapping[\"price.value\"].mapping.destination",
+        InvalidConfigReason::InvalidColumnName("price value".to_string()),
+    );
+
+    let config = config_with_mapping(json!({
+        "items": {"type": "table", "destination": "", "tableMapping": {}}
+    }));
+    assert_invalid(
+        &config,
+        "parameters.mapping.items.destination",
+        InvalidConfigReason::EmptyDestination,
+    );
+}
+
+#[test]
+fn test_primary_key_on_table() {
+    let config = config_with_mapping(json!({
+        "items": {"type": "table", "destination": "items", "primary_key": true, "tableMapping": {}}
+    }));
+
+    assert_invalid(
+        &config,
+        "parameters.mapping.items.primary_key",
+        InvalidConfigReason::PrimaryKeyOnTable,
+    );
+}
+
+#[test]
+fn test_invalid_root_node() {
+    let mut config = config_with_mapping(json!({}));
+    config.parameters.root_node = "data..items".to_string();
+
+    assert_invalid(
+        &config,
+        "parameters.root_node",
+        InvalidConfigReason::InvalidRootNode("data..items".to_string()),
+    );
+}
+
+#[test]
+fn test_root_nodes() {
+    let mut config = config_with_mapping(json!({}));
+    config.parameters.root_nodes = serde_json::from_value(json!([
+        {"root_node": "data.customers", "destination": "customers"},
+        {"root_node": "data.invoices", "destination": "invoices"}
+    ]))
+    .unwrap();
+    assert!(config.validate().is_ok());
+
+    config.parameters.root_nodes[1].destination = "customers".to_string();
+    assert_invalid(
+        &config,
+        "parameters.root_nodes[1].destination",
+        InvalidConfigReason::DuplicateTable("customers".to_string()),
+    );
+
+    config.parameters.root_nodes[1].destination = "invoices".to_string();
+    config.parameters.root_nodes[1].root_node = "data[".to_string();
+    assert_invalid(
+        &config,
+        "parameters.root_nodes[1].root_node",
+        InvalidConfigReason::InvalidRootNode("data[".to_string()),
+    );
+
+    config.parameters.root_nodes[1].root_node = "data.invoices".to_string();
+    config.parameters.root_node = "data".to_string();
+    assert_invalid(
+        &config,
+        "parameters.root_nodes",
+        InvalidConfigReason::ConflictingRootNodes,
+    );
+}
+
+#[test]
+fn test_invalid_csv_dialect() {
+    let mut config = config_with_mapping(json!({}));
+    config.parameters.csv.delimiter = '\n';
+    assert_invalid(
+        &config,
+        "parameters.csv.delimiter",
+        InvalidConfigReason::InvalidCsvCharacter('\n'),
+    );
+
+    config.parameters.csv.delimiter = '"';
+    assert_invalid(
+        &config,
+        "parameters.csv.quote",
+        InvalidConfigReason::DelimiterEqualsQuote,
+    );
+}
+
+#[test]
+fn test_invalid_compression_level() {
+    let mut config = config_with_mapping(json!({}));
+    config.parameters.compression.level = 10;
+
+    assert_invalid(
+        &config,
+        "parameters.compression.level",
+        InvalidConfigReason::InvalidCompressionLevel(10),
+    );
+}
+
+#[test]
+fn test_empty_slice() {
+    let mut config = config_with_mapping(json!({}));
+    config.parameters.slicing.max_rows = Some(0);
+
+    assert_invalid(
+        &config,
+        "parameters.slicing.max_rows",
+        InvalidConfigReason::EmptySlice,
+    );
+}
+
+#[test]
+fn test_invalid_glob() {
+    let mut config = config_with_mapping(json!({}));
+    config.parameters.input_files.exclude = vec!["*.json".to_string(), "data/[a-".to_string()];
+
+    assert_invalid(
+        &config,
+        "parameters.input_files.exclude[1]",
+        InvalidConfigReason::InvalidGlob("data/[a-".to_string()),
+    );
+}
+
+#[test]
+fn test_invalid_csv_input() {
+    let mut config = config_with_mapping(json!({}));
+    config.parameters.csv_input = Some(CsvInput {
+        json_column: String::new(),
+        carry_columns: Vec::new(),
+    });
+    assert_invalid(
+        &config,
+        "parameters.csv_input.json_column",
+        InvalidConfigReason::EmptyColumn,
+    );
+
+    config.parameters.csv_input = Some(CsvInput {
+        json_column: "payload".to_string(),
+        carry_columns: vec!["id".to_string(), "id".to_string()],
+    });
+    assert_invalid(
+        &config,
+        "parameters.csv_input.carry_columns[1]",
+        InvalidConfigReason::DuplicateColumn("id".to_string()),
+    );
+}
+
+#[test]
+fn test_generated_key_column() {
+    // Without a primary key the row keeps the child rows' key in an `items` column
+    let config = config_with_mapping(json!({
+        "x": {"type": "column", "mapping": {"destination": "items"}},
+        "items": {"type": "table", "destination": "items", "tableMapping": {}}
+    }));
+    assert_invalid(
+        &config,
+        "parameters.mapping.items",
+        InvalidConfigReason::KeyColumnConflict("items".to_string()),
+    );
+
+    let config = config_with_mapping(json!({
+        "id": {"type": "column", "mapping": {"destination": "id"}},
+        "order-item": {"type": "table", "destination": "items", "tableMapping": {}}
+    }));
+    assert_invalid(
+        &config,
+        "parameters.mapping.order-item",
+        InvalidConfigReason::InvalidKeyColumn("order-item".to_string()),
+    );
+
+    // A primary key is used as the key instead
+    let config = config_with_mapping(json!({
+        "id": {"type": "column", "mapping": {"destination": "id", "primary_key": true}},
+        "x": {"type": "column", "mapping": {"destination": "items"}},
+        "order-item": {"type": "table", "destination": "items", "tableMapping": {}}
+    }));
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn test_infers_tables() {
+    let mapped = json!({
+        "id": {"type": "column", "mapping": {"destination": "order_id", "primary_key": true}},
+        "order-item": {
+            "type": "table",
+            "destination": "order-items",
+            "tableMapping": {
+                "item": {"type": "column", "mapping": {"destination": "item_name"}}
+            }
+        }
+    });
+    let mut config = config_with_mapping(mapped.clone());
+    assert!(!config.parameters.infers_tables());
+
+    assert!(config_with_mapping(json!({})).parameters.infers_tables());
+    assert!(config_with_mapping(json!({
+        "id": {"type": "column", "mapping": {"destination": "order_id"}},
+        "order-item": {"type": "table", "destination": "order-items", "tableMapping": {}}
+    }))
+    .parameters
+    .infers_tables());
+
+    config.parameters.mapping.clear();
+    config.parameters.root_nodes = serde_json::from_value(json!([
+        {"root_node": "data.customers", "destination": "customers", "mapping": mapped},
+        {"root_node": "data.invoices", "destination": "invoices"}
+    ]))
+    .unwrap();
+    assert!(config.parameters.infers_tables());
+    config.parameters.root_nodes.pop();
+    assert!(!config.parameters.infers_tables());
+}
@@ -1,9 +1,14 @@
 use anyhow::Result;
-use json2csv_processor::config::{Config, InputType, Parameters};
+use indexmap::IndexMap;
+use json2csv_processor::config::{
+    BooleanFormat, CompressionFormat, CompressionOptions, Config, CsvInput, CsvOptions,
+    FlattenOptions, FormatOptions, InputType, LineTerminator, MappingType, Parameters, QuoteStyle,
+    RootNode, SlicingOptions, TypeConflictPolicy,
+};
 use json2csv_processor::parser::Parser;
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
 fn setup_test_dir(test_name: &str) -> Result<PathBuf> {
@@ -28,8 +33,12 @@ fn setup_test_dir(test_name: &str) -> Result<PathBuf> {
         }
     }
 
-    // Create output directory
-    fs::create_dir_all(test_dir.join("out/tables"))?;
+    // Start from an empty output directory, stale tables must not pass for new ones
+    let out_tables = test_dir.join("out/tables");
+    if out_tables.exists() {
+        fs::remove_dir_all(&out_tables)?;
+    }
+    fs::create_dir_all(&out_tables)?;
 
     // Create expected directory and write expected files
     fs::create_dir_all(test_dir.join("expected"))?;
@@ -44,26 +53,211 @@ fn setup_test_dir(test_name: &str) -> Result<PathBuf> {
         "basic-sample-2-tables" => {
             fs::write(
                 test_dir.join("expected/root.csv"),
-                "id,name\n\"1\",\"First\"\n",
+                "id,name,items\n\"1\",\"First\",\"root_cd6163016fdd279c7d921aec71e84e43\"\n",
             )?;
-            fs::write(test_dir.join("expected/items.csv"), "item_id,quantity,JSON_parentId\n\"1\",\"10\",\"items_0\"\n\"2\",\"20\",\"items_1 \"\n")?;
+            fs::write(test_dir.join("expected/root_items.csv"), "item_id,quantity,JSON_parentId\n\"1\",\"10\",\"root_cd6163016fdd279c7d921aec71e84e43\"\n\"2\",\"20\",\"root_cd6163016fdd279c7d921aec71e84e43\"\n")?;
         }
         "basic-sample-2-tables-root-el" => {
-            fs::write(test_dir.join("expected/root.csv"), "id\n\"1\"\n")?;
             fs::write(
-                test_dir.join("expected/items.csv"),
-                "item_id,quantity,JSON_parentId\n\"2 \",\"\",\"items_0\"\n",
+                test_dir.join("expected/root.csv"),
+                "id,items\n\"1\",\"data_8bc902f77a6671bafd0f39f7c9aa0bea\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_items.csv"),
+                "id,JSON_parentId\n\"2 \",\"data_8bc902f77a6671bafd0f39f7c9aa0bea\"\n",
             )?;
         }
         "sample-2-tables-add-file-name" => {
             fs::write(
                 test_dir.join("expected/root.csv"),
-                "id,name,keboola_file_name_col\n\"1\",\"Test\",\"sample.json \"\n",
+                "id,name,items,keboola_file_name_col\n\"1\",\"Test\",\"root_9b01ced61575bba8de9df5df41f2e93b\",\"sample.json\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_items.csv"),
+                "id,quantity,JSON_parentId\n\"A\",\"10\",\"root_9b01ced61575bba8de9df5df41f2e93b\"\n\"B\",\"20\",\"root_9b01ced61575bba8de9df5df41f2e93b\"\n",
             )?;
-            fs::write(test_dir.join("expected/items.csv"), "item_id,quantity,JSON_parentId\n\"A\",\"10\",\"items_0\"\n\"B\",\"20\",\"items_1 \"\n")?;
         }
         "sample-2-tables-root-el-mapping" => {
-            fs::write(test_dir.join("expected/order_items.csv"), "item_id,quantity,order_id\n\"A\",\"10\",\"1\"\n\"B\",\"20\",\"1\"\n\"C\",\"30\",\"2 \"\n")?;
+            fs::write(test_dir.join("expected/order_items.csv"), "item_id,quantity,order_id\n\"A\",\"10\",\"1\"\n\"B\",\"20\",\"1\"\n\"C\",\"30\",\"2\"\n")?;
+        }
+        "sample-nested-mapping" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "order_id,customer_name\n\"1\",\"Alice\"\n\"3\",\"Bob\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/order_items.csv"),
+                "item_id,order_id\n\"A\",\"1\"\n\"B\",\"1\"\n\"C\",\"3\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/item_discounts.csv"),
+                "discount_code,item_id\n\"X\",\"A\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/order_payments.csv"),
+                "amount,order_id\n\"100\",\"1\"\n\"50\",\"3\"\n",
+            )?;
+        }
+        "sample-parent-key-field" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,items\n\"1\",\"1\"\n\"2\",\"2\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_items.csv"),
+                "sku,JSON_parentId\n\"A\",\"1\"\n\"B\",\"2\"\n",
+            )?;
+        }
+        "sample-nested-objects" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "item,price_xml_attr_currency,price_txt_content_,price_discount\n\"Umbrella\",\"CZK\",\"100\",\"{\"\"percent\"\":10}\"\n\"Rain Coat\",\"CZK\",\"200\",\"\"\n",
+            )?;
+        }
+        "sample-streaming" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,name,items,note\n\"1\",\"A\",\"1\",\"\"\n\"2\",\"B\",\"\",\"\"\n\"3\",\"C\",\"3\",\"late\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_items.csv"),
+                "sku,JSON_parentId,qty\n\"x\",\"1\",\"\"\n\"y\",\"3\",\"2\"\n",
+            )?;
+        }
+        "sample-ndjson" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,name,tags\n\"1\",\"A\",\"\"\n\"2\",\"B\",\"2\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_tags.csv"),
+                "tag,JSON_parentId\n\"x\",\"2\"\n",
+            )?;
+        }
+        "sample-scalar-arrays" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,name,tags,scores\n\"1\",\"A\",\"1\",\"1\"\n\"2\",\"B\",\"2\",\"\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_tags.csv"),
+                "data,JSON_parentId\n\"red\",\"1\"\n\"blue\",\"1\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_scores.csv"),
+                "data,JSON_parentId\n\"1\",\"1\"\n\"2.5\",\"1\"\n\"\",\"1\"\n",
+            )?;
+        }
+        "sample-scalar-arrays-mapping" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,tags\n\"1\",\"red|blue\"\n\"2\",\"\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/scores.csv"),
+                "score,record_id\n\"1\",\"1\"\n\"2.5\",\"1\"\n\"\",\"1\"\n",
+            )?;
+        }
+        "sample-inconsistent-shapes" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,price,order-item\n\"1\",\"{\"\"amount\"\":100,\"\"currency\"\":\"\"CZK\"\"}\",\"1\"\n\"2\",\"50\",\"2\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_order-item.csv"),
+                "item,JSON_parentId\n\"Umbrella\",\"1\"\n\"Rain Coat\",\"1\"\n\"Sun Screen\",\"2\"\n",
+            )?;
+        }
+        "sample-inconsistent-shapes-ndjson" | "sample-inconsistent-shapes-streaming" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,price,order-item\n\"1\",\"50\",\"1\"\n\"2\",\"{\"\"amount\"\":100,\"\"currency\"\":\"\"CZK\"\"}\",\"2\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_order-item.csv"),
+                "item,JSON_parentId\n\"Sun Screen\",\"1\"\n\"Umbrella\",\"2\"\n\"Rain Coat\",\"2\"\n",
+            )?;
+        }
+        "sample-root-node-wildcard" | "sample-root-node-wildcard-streaming" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,name\n\"1\",\"A\"\n\"2\",\"B\"\n\"3\",\"C\"\n",
+            )?;
+        }
+        "sample-root-nodes" | "sample-root-nodes-streaming" => {
+            fs::write(
+                test_dir.join("expected/customers.csv"),
+                "id,name,items\n\"1\",\"A\",\"1\"\n\"2\",\"B\",\"2\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/customers_items.csv"),
+                "sku,JSON_parentId\n\"x\",\"1\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/invoices.csv"),
+                "invoice_number,total\n\"I-1\",\"10\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/invoice_items.csv"),
+                "sku,quantity,invoice_number\n\"x\",\"2\",\"I-1\"\n",
+            )?;
+        }
+        "sample-value-format" | "sample-value-format-streaming" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,price,exp,small,ok,none\n\"12345678901234567890123\",\"1.10\",\"1e+3\",\"0.1\",\"1\",\"NULL\"\n\"2\",\"-0.5\",\"2e-7\",\"1.7976931348623157e+309\",\"0\",\"1\"\n",
+            )?;
+        }
+        "sample-json-column" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,customer,city,tags,note\n\"1\",\"{\"\"name\"\":\"\"A\"\",\"\"address\"\":{\"\"city\"\":\"\"Prague\"\"}}\",\"\"\"Prague\"\"\",\"[\"\"x\"\",1.50,null]\",\"\"\"hi\"\"\"\n\"2\",\"{}\",\"\",\"[]\",\"\"\n",
+            )?;
+        }
+        "sample-parallel" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,name,tags,score\n\"1\",\"A\",\"1\",\"\"\n\"2\",\"B\",\"\",\"\"\n\"3\",\"C\",\"\",\"1.5\"\n\"5\",\"\",\"5\",\"2\"\n\"6\",\"F\",\"\",\"\"\n\"7\",\"G\",\"7\",\"\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_tags.csv"),
+                "data,JSON_parentId\n\"x\",\"1\"\n\"w\",\"7\"\n",
+            )?;
+        }
+        "sample-parallel-shapes" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,order-item\n\"1\",\"1\"\n\"2\",\"2\"\n\"3\",\"3\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_order-item.csv"),
+                "item,JSON_parentId\n\"Umbrella\",\"1\"\n\"Rain Coat\",\"2\"\n\"Boots\",\"2\"\n\"Sun Screen\",\"3\"\n",
+            )?;
+        }
+        "sample-compressed" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,keboola_file_name_col\n\"1\",\"orders.json.gz\"\n\"2\",\"orders.json.zst\"\n\"3\",\"events.jsonl.bz2\"\n\"4\",\"events.jsonl.bz2\"\n\"5\",\"orders.json.xz\"\n\"6\",\"disguised.json\"\n\"7\",\"a.json\"\n\"8\",\"b.json.gz\"\n\"9\",\"c.json\"\n",
+            )?;
+        }
+        "sample-gzip-output" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "id,name,note\n\"1\",\"A\",\"\"\n\"2\",\"B\",\"late\"\n",
+            )?;
+        }
+        "sample-csv-input" => {
+            fs::write(
+                test_dir.join("expected/root.csv"),
+                "name,items,id\n\"A\",\"A\",\"101\"\n\"C\",\"C\",\"103\"\n",
+            )?;
+            fs::write(
+                test_dir.join("expected/root_items.csv"),
+                "sku,JSON_parentId\n\"x\",\"A\"\n\"y\",\"A\"\n",
+            )?;
+        }
+        "sample-failed-file-rollback" => {
+            fs::write(test_dir.join("expected/root.csv"), "id,name\n\"1\",\"A\"\n")?;
         }
         _ => {}
     }
@@ -125,7 +319,8 @@ fn test_basic_sample_2_files() -> Result<()> {
             root_node: String::new(),
             incremental: false,
             add_file_name: false,
-            mapping: HashMap::new(),
+            mapping: IndexMap::new(),
+            ..Default::default()
         },
     };
 
@@ -172,7 +367,8 @@ fn test_basic_sample_2_tables() -> Result<()> {
             root_node: String::new(),
             incremental: false,
             add_file_name: false,
-            mapping: HashMap::new(),
+            mapping: IndexMap::new(),
+            ..Default::default()
         },
     };
 
@@ -207,8 +403,8 @@ fn test_basic_sample_2_tables() -> Result<()> {
     )?;
 
     compare_csv_files(
-        &test_dir.join("out/tables/items.csv"),
-        &PathBuf::from("tests/functional/basic-sample-2-tables/expected/items.csv"),
+        &test_dir.join("out/tables/root_items.csv"),
+        &PathBuf::from("tests/functional/basic-sample-2-tables/expected/root_items.csv"),
     )?;
 
     Ok(())
@@ -224,7 +420,8 @@ fn test_sample_with_root_node() -> Result<()> {
             root_node: "data".to_string(),
             incremental: false,
             add_file_name: false,
-            mapping: HashMap::new(),
+            mapping: IndexMap::new(),
+            ..Default::default()
         },
     };
 
@@ -255,8 +452,8 @@ fn test_sample_with_root_node() -> Result<()> {
     )?;
 
     compare_csv_files(
-        &test_dir.join("out/tables/items.csv"),
-        &PathBuf::from("tests/functional/basic-sample-2-tables-root-el/expected/items.csv"),
+        &test_dir.join("out/tables/root_items.csv"),
+        &PathBuf::from("tests/functional/basic-sample-2-tables-root-el/expected/root_items.csv"),
     )?;
 
     Ok(())
@@ -272,7 +469,8 @@ fn test_sample_with_file_name() -> Result<()> {
             root_node: String::new(),
             incremental: false,
             add_file_name: true,
-            mapping: HashMap::new(),
+            mapping: IndexMap::new(),
+            ..Default::default()
         },
     };
 
@@ -307,8 +505,8 @@ fn test_sample_with_file_name() -> Result<()> {
     )?;
 
     compare_csv_files(
-        &test_dir.join("out/tables/items.csv"),
-        &PathBuf::from("tests/functional/sample-2-tables-add-file-name/expected/items.csv"),
+        &test_dir.join("out/tables/root_items.csv"),
+        &PathBuf::from("tests/functional/sample-2-tables-add-file-name/expected/root_items.csv"),
     )?;
 
     Ok(())
@@ -318,7 +516,7 @@ fn test_sample_with_file_name() -> Result<()> {
 fn test_sample_with_mapping() -> Result<()> {
     let test_dir = setup_test_dir("sample-2-tables-root-el-mapping")?;
 
-    let mut mapping = HashMap::new();
+    let mut mapping = IndexMap::new();
     let json_mapping = json!({
         "id": {
             "type": "column",
@@ -366,6 +564,7 @@ fn test_sample_with_mapping() -> Result<()> {
             incremental: false,
             add_file_name: false,
             mapping,
+            ..Default::default()
         },
     };
 
@@ -413,3 +612,1486 @@ fn test_sample_with_mapping() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_sample_with_manifest() -> Result<()> {
+    let test_dir = setup_test_dir("sample-2-tables-manifest")?;
+
+    let mut mapping = IndexMap::new();
+    let json_mapping = json!({
+        "items": {
+            "type": "table",
+            "destination": "order_items",
+            "parent_key": {
+                "destination": "order_id",
+                "primary_key": true
+            },
+            "tableMapping": {
+                "id": {
+                    "type": "column",
+                    "mapping": {
+                        "destination": "item_id",
+                        "primary_key": true
+                    }
+                },
+                "quantity": {
+                    "type": "column",
+                    "mapping": {
+                        "destination": "quantity"
+                    }
+                }
+            }
+        }
+    });
+
+    if let Value::Object(obj) = json_mapping {
+        for (key, value) in obj {
+            mapping.insert(key, serde_json::from_value(value)?);
+        }
+    }
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Tables,
+            root_node: "data".to_string(),
+            incremental: true,
+            add_file_name: false,
+            mapping,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    fs::write(
+        test_dir.join("in/tables/sample.json"),
+        json!({
+            "data": [
+                {
+                    "id": "1",
+                    "items": [
+                        {
+                            "id": "A",
+                            "quantity": 10
+                        }
+                    ]
+                }
+            ]
+        })
+        .to_string(),
+    )?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/tables/sample.json"))?;
+    parser.write_tables()?;
+
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(
+        test_dir.join("out/tables/order_items.csv.manifest"),
+    )?)?;
+    assert_eq!(manifest["incremental"], json!(true));
+    assert_eq!(manifest["primary_key"], json!(["item_id", "order_id"]));
+    assert_eq!(
+        manifest["columns"],
+        json!(["item_id", "quantity", "order_id"])
+    );
+    assert_eq!(
+        manifest["column_metadata"],
+        json!({
+            "item_id": [{"key": "KBC.datatype.basetype", "value": "STRING"}],
+            "quantity": [{"key": "KBC.datatype.basetype", "value": "INTEGER"}],
+            "order_id": [{"key": "KBC.datatype.basetype", "value": "STRING"}]
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_mapping_without_primary_key_keeps_generated_key() -> Result<()> {
+    let test_dir = setup_test_dir("sample-mapping-generated-key")?;
+
+    let mapping: IndexMap<String, MappingType> = serde_json::from_value(json!({
+        "id": {"type": "column", "mapping": {"destination": "id"}},
+        "items": {
+            "type": "table",
+            "destination": "items",
+            "tableMapping": {
+                "sku": {"type": "column", "mapping": {"destination": "sku"}}
+            }
+        }
+    }))?;
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            mapping,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    // The parent rows hold the generated keys the child rows point to
+    let read_rows = |table: &str| -> Result<Vec<csv::StringRecord>> {
+        let mut reader =
+            csv::Reader::from_path(test_dir.join(format!("out/tables/{}.csv", table)))?;
+        assert_eq!(
+            reader.headers()?,
+            if table == "root" {
+                vec!["id", "items"]
+            } else {
+                vec!["sku", "JSON_parentId"]
+            }
+        );
+        Ok(reader.records().collect::<Result<_, _>>()?)
+    };
+    let root = read_rows("root")?;
+    let items = read_rows("items")?;
+    let parent_keys: Vec<&str> = items.iter().map(|row| &row[1]).collect();
+    assert!(root[0][1].starts_with("root_"));
+    assert_ne!(root[0][1], root[1][1]);
+    assert_eq!(parent_keys, [&root[0][1], &root[0][1], &root[1][1]]);
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_nested_mapping() -> Result<()> {
+    let test_dir = setup_test_dir("sample-nested-mapping")?;
+
+    let mapping: IndexMap<String, MappingType> = serde_json::from_value(json!({
+        "id": {
+            "type": "column",
+            "mapping": {
+                "destination": "order_id",
+                "primary_key": true
+            }
+        },
+        "customer.name": {
+            "type": "column",
+            "mapping": {
+                "destination": "customer_name"
+            }
+        },
+        "items": {
+            "type": "table",
+            "destination": "order_items",
+            "parent_key": {
+                "destination": "order_id"
+            },
+            "tableMapping": {
+                "id": {
+                    "type": "column",
+                    "mapping": {
+                        "destination": "item_id",
+                        "primary_key": true
+                    }
+                },
+                "discounts": {
+                    "type": "table",
+                    "destination": "item_discounts",
+                    "parent_key": {
+                        "destination": "item_id"
+                    },
+                    "tableMapping": {
+                        "code": {
+                            "type": "column",
+                            "mapping": {
+                                "destination": "discount_code"
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        "payments": {
+            "type": "table",
+            "destination": "order_payments",
+            "parent_key": {
+                "destination": "order_id"
+            },
+            "tableMapping": {
+                "amount": {
+                    "type": "column",
+                    "mapping": {
+                        "destination": "amount"
+                    }
+                }
+            }
+        }
+    }))?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Tables,
+            root_node: "data".to_string(),
+            incremental: false,
+            add_file_name: false,
+            mapping,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/tables/sample.json"))?;
+    parser.write_tables()?;
+
+    for table in ["root", "order_items", "item_discounts", "order_payments"] {
+        compare_csv_files(
+            &test_dir.join(format!("out/tables/{}.csv", table)),
+            &PathBuf::from(format!(
+                "tests/functional/sample-nested-mapping/expected/{}.csv",
+                table
+            )),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_parent_key_field() -> Result<()> {
+    let test_dir = setup_test_dir("sample-parent-key-field")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            root_node: "data".to_string(),
+            parent_key_field: Some("id".to_string()),
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root.csv"),
+        &PathBuf::from("tests/functional/sample-parent-key-field/expected/root.csv"),
+    )?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root_items.csv"),
+        &PathBuf::from("tests/functional/sample-parent-key-field/expected/root_items.csv"),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_readme_mapping_manifests() -> Result<()> {
+    let test_dir = setup_test_dir("sample-readme-mapping")?;
+
+    // Advanced Example 1 of the README, with the Generic Extractor spelling
+    let config: Config = serde_json::from_value(json!({
+        "definition": {
+            "component": "kds-team.processor-json2csv"
+        },
+        "parameters": {
+            "mapping": {
+                "id": {
+                    "type": "column",
+                    "mapping": {"destination": "order_id", "primaryKey": true}
+                },
+                "date": {
+                    "type": "column",
+                    "mapping": {"destination": "order_date"}
+                },
+                "cust_name": {
+                    "type": "column",
+                    "mapping": {"destination": "customer_name"}
+                },
+                "order-item": {
+                    "type": "table",
+                    "destination": "order-items",
+                    "parentKey": {"primaryKey": true, "destination": "order_id"},
+                    "tableMapping": {
+                        "row_nr": {
+                            "type": "column",
+                            "mapping": {"destination": "row_nr", "primaryKey": true}
+                        },
+                        "price.xml_attr_currency": {
+                            "type": "column",
+                            "mapping": {"destination": "currency"}
+                        },
+                        "price.txt_content_": {
+                            "type": "column",
+                            "mapping": {"destination": "price_value"}
+                        },
+                        "item": {
+                            "type": "column",
+                            "mapping": {"destination": "item_name"}
+                        }
+                    }
+                }
+            },
+            "incremental": true,
+            "root_node": "root_el.orders.order",
+            "in_type": "files"
+        }
+    }))?;
+    config.validate()?;
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    for (table, primary_key) in [
+        ("root", json!(["order_id"])),
+        ("order-items", json!(["row_nr", "order_id"])),
+    ] {
+        let manifest: Value = serde_json::from_str(&fs::read_to_string(
+            test_dir.join(format!("out/tables/{}.csv.manifest", table)),
+        )?)?;
+        assert_eq!(manifest["incremental"], json!(true));
+        assert_eq!(manifest["primary_key"], primary_key, "{}", table);
+        // The ids are JSON strings, on both sides of the join
+        assert_eq!(
+            manifest["column_metadata"]["order_id"],
+            json!([{"key": "KBC.datatype.basetype", "value": "STRING"}]),
+            "{}",
+            table
+        );
+    }
+
+    let order_items = fs::read_to_string(test_dir.join("out/tables/order-items.csv"))?;
+    assert!(order_items.starts_with("row_nr,currency,price_value,item_name,order_id\n"));
+    assert!(order_items.contains("\"Sun Screen\",\"2\"\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_child_tables_are_named_after_their_parent_table() -> Result<()> {
+    let test_dir = setup_test_dir("sample-nested-equal-keys")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    // `items` holds items of the root records and of the items themselves
+    fs::create_dir_all(test_dir.join("in/files"))?;
+    fs::write(
+        test_dir.join("in/files/sample.json"),
+        json!([{"items": [{"sku": "A", "items": [{"x": 1}, {"x": 2}]}]}]).to_string(),
+    )?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    let mut items = csv::Reader::from_path(test_dir.join("out/tables/root_items.csv"))?;
+    assert_eq!(items.headers()?, vec!["sku", "items", "JSON_parentId"]);
+    assert_eq!(items.records().count(), 1);
+
+    let mut nested = csv::Reader::from_path(test_dir.join("out/tables/root_items_items.csv"))?;
+    assert_eq!(nested.headers()?, vec!["x", "JSON_parentId"]);
+    assert_eq!(nested.records().count(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_generated_parent_keys_are_unique_across_files() -> Result<()> {
+    let test_dir = setup_test_dir("sample-2-files-parent-keys")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    // Both files carry identical content, only the file differs
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample1.json"))?;
+    parser.process_file(&test_dir.join("in/files/sample2.json"))?;
+    parser.write_tables()?;
+
+    let mut root = csv::Reader::from_path(test_dir.join("out/tables/root.csv"))?;
+    let root_keys: Vec<String> = root
+        .records()
+        .map(|record| Ok(record?[2].to_string()))
+        .collect::<Result<_>>()?;
+    assert_eq!(root_keys.len(), 2);
+    assert_ne!(root_keys[0], root_keys[1]);
+    assert!(root_keys[0].starts_with("root_"));
+
+    let mut items = csv::Reader::from_path(test_dir.join("out/tables/root_items.csv"))?;
+    let item_keys: Vec<String> = items
+        .records()
+        .map(|record| Ok(record?[1].to_string()))
+        .collect::<Result<_>>()?;
+    assert_eq!(item_keys, root_keys);
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_nested_objects() -> Result<()> {
+    let test_dir = setup_test_dir("sample-nested-objects")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            flatten: FlattenOptions {
+                max_depth: Some(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root.csv"),
+        &PathBuf::from("tests/functional/sample-nested-objects/expected/root.csv"),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_streaming() -> Result<()> {
+    let test_dir = setup_test_dir("sample-streaming")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            root_node: "data.records".to_string(),
+            parent_key_field: Some("id".to_string()),
+            streaming: true,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    // Both tables gain a column after their first rows were written
+    compare_csv_files(
+        &test_dir.join("out/tables/root.csv"),
+        &PathBuf::from("tests/functional/sample-streaming/expected/root.csv"),
+    )?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root_items.csv"),
+        &PathBuf::from("tests/functional/sample-streaming/expected/root_items.csv"),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_ndjson() -> Result<()> {
+    let test_dir = setup_test_dir("sample-ndjson")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            root_node: "record".to_string(),
+            parent_key_field: Some("id".to_string()),
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.jsonl"))?;
+    parser.write_tables()?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root.csv"),
+        &PathBuf::from("tests/functional/sample-ndjson/expected/root.csv"),
+    )?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root_tags.csv"),
+        &PathBuf::from("tests/functional/sample-ndjson/expected/root_tags.csv"),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_ndjson_error_reports_line_number() -> Result<()> {
+    let test_dir = setup_test_dir("sample-ndjson-broken")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            root_node: "record".to_string(),
+            ..Default::default()
+        },
+    };
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    let error = parser
+        .process_file(&test_dir.join("in/files/broken.ndjson"))
+        .unwrap_err();
+    assert!(format!("{:#}", error).starts_with("Invalid JSON on line 3"));
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_file_is_rolled_back() -> Result<()> {
+    let test_dir = setup_test_dir("sample-failed-file-rollback")?;
+
+    for streaming in [false, true] {
+        let config = Config {
+            parameters: Parameters {
+                in_type: InputType::Files,
+                parent_key_field: Some("id".to_string()),
+                streaming,
+                ..Default::default()
+            },
+        };
+
+        let mut parser = Parser::new(config, test_dir.join("out/tables"));
+        parser.process_file(&test_dir.join("in/files/sample1.json"))?;
+        // The broken file fails after producing rows and a new table
+        assert!(parser
+            .process_file(&test_dir.join("in/files/sample2.json"))
+            .is_err());
+        parser.write_tables()?;
+
+        compare_csv_files(
+            &test_dir.join("out/tables/root.csv"),
+            &PathBuf::from("tests/functional/sample-failed-file-rollback/expected/root.csv"),
+        )?;
+        assert!(!test_dir.join("out/tables/root_tags.csv").exists());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_scalar_arrays() -> Result<()> {
+    let test_dir = setup_test_dir("sample-scalar-arrays")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            parent_key_field: Some("id".to_string()),
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    for table in ["root", "root_tags", "root_scores"] {
+        compare_csv_files(
+            &test_dir.join(format!("out/tables/{}.csv", table)),
+            &PathBuf::from(format!(
+                "tests/functional/sample-scalar-arrays/expected/{}.csv",
+                table
+            )),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_key_with_scalar_and_object_items_keeps_one_table() -> Result<()> {
+    let test_dir = setup_test_dir("sample-mixed-array-items")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            parent_key_field: Some("id".to_string()),
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    fs::create_dir_all(test_dir.join("in/files"))?;
+    fs::write(
+        test_dir.join("in/files/sample.json"),
+        json!([
+            {"id": 1, "tags": ["a"]},
+            {"id": 2, "tags": [{"name": "b"}]},
+            {"id": 3, "tags": ["c", {"name": "d"}]}
+        ])
+        .to_string(),
+    )?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    // Scalar and object items of `tags` are written to the same child table
+    let mut tables: Vec<String> = fs::read_dir(test_dir.join("out/tables"))?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<_>>()?;
+    tables.retain(|name| name.ends_with(".csv"));
+    tables.sort();
+    assert_eq!(tables, vec!["root.csv", "root_tags.csv"]);
+
+    let mut tags = csv::Reader::from_path(test_dir.join("out/tables/root_tags.csv"))?;
+    let parent_ids: Vec<String> = tags
+        .records()
+        .map(|record| Ok(record?[1].to_string()))
+        .collect::<Result<_>>()?;
+    assert_eq!(parent_ids, vec!["1", "2", "3", "3"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_scalar_arrays_mapping() -> Result<()> {
+    let test_dir = setup_test_dir("sample-scalar-arrays-mapping")?;
+
+    let mapping: IndexMap<String, MappingType> = serde_json::from_value(json!({
+        "id": {
+            "type": "column",
+            "mapping": {
+                "destination": "id",
+                "primary_key": true
+            }
+        },
+        "tags": {
+            "type": "column",
+            "mapping": {
+                "destination": "tags",
+                "delimiter": "|"
+            }
+        },
+        "scores": {
+            "type": "table",
+            "destination": "scores",
+            "parent_key": {
+                "destination": "record_id"
+            },
+            "tableMapping": {
+                "data": {
+                    "type": "column",
+                    "mapping": {
+                        "destination": "score"
+                    }
+                }
+            }
+        }
+    }))?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            mapping,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    for table in ["root", "scores"] {
+        compare_csv_files(
+            &test_dir.join(format!("out/tables/{}.csv", table)),
+            &PathBuf::from(format!(
+                "tests/functional/sample-scalar-arrays-mapping/expected/{}.csv",
+                table
+            )),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_inconsistent_shapes() -> Result<()> {
+    let test_dir = setup_test_dir("sample-inconsistent-shapes")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            root_node: "orders".to_string(),
+            parent_key_field: Some("id".to_string()),
+            on_type_conflict: TypeConflictPolicy::Stringify,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    // The single `order-item` object joins the items of the other order
+    for table in ["root", "root_order-item"] {
+        compare_csv_files(
+            &test_dir.join(format!("out/tables/{}.csv", table)),
+            &PathBuf::from(format!(
+                "tests/functional/sample-inconsistent-shapes/expected/{}.csv",
+                table
+            )),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_inconsistent_shapes_per_record() -> Result<()> {
+    // Records converted one at a time, the single object comes before the array
+    for (test_name, file, root_node, streaming) in [
+        (
+            "sample-inconsistent-shapes-ndjson",
+            "sample.jsonl",
+            "",
+            false,
+        ),
+        (
+            "sample-inconsistent-shapes-streaming",
+            "sample.json",
+            "orders",
+            true,
+        ),
+    ] {
+        let test_dir = setup_test_dir(test_name)?;
+
+        let config = Config {
+            parameters: Parameters {
+                in_type: InputType::Files,
+                root_node: root_node.to_string(),
+                parent_key_field: Some("id".to_string()),
+                on_type_conflict: TypeConflictPolicy::Stringify,
+                streaming,
+                ..Default::default()
+            },
+        };
+
+        create_config(&test_dir, &config)?;
+
+        let mut parser = Parser::new(config, test_dir.join("out/tables"));
+        parser.process_file(&test_dir.join("in/files").join(file))?;
+        parser.write_tables()?;
+
+        for table in ["root", "root_order-item"] {
+            compare_csv_files(
+                &test_dir.join(format!("out/tables/{}.csv", table)),
+                &PathBuf::from(format!(
+                    "tests/functional/{}/expected/{}.csv",
+                    test_name, table
+                )),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_type_conflict_error() -> Result<()> {
+    let test_dir = setup_test_dir("sample-type-conflict-error")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            root_node: "orders".to_string(),
+            on_type_conflict: TypeConflictPolicy::Error,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    let err = parser
+        .process_file(&test_dir.join("in/files/sample.json"))
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("'orders.price'"),
+        "unexpected error: {err}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_root_node_wildcard() -> Result<()> {
+    for (test_name, streaming) in [
+        ("sample-root-node-wildcard", false),
+        ("sample-root-node-wildcard-streaming", true),
+    ] {
+        let test_dir = setup_test_dir(test_name)?;
+
+        let config = Config {
+            parameters: Parameters {
+                in_type: InputType::Files,
+                root_node: "$.data.pages[*].records".to_string(),
+                streaming,
+                ..Default::default()
+            },
+        };
+
+        create_config(&test_dir, &config)?;
+
+        let mut parser = Parser::new(config, test_dir.join("out/tables"));
+        parser.process_file(&test_dir.join("in/files/sample.json"))?;
+        parser.write_tables()?;
+
+        compare_csv_files(
+            &test_dir.join("out/tables/root.csv"),
+            &PathBuf::from(format!("tests/functional/{}/expected/root.csv", test_name)),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_quoted_root_node() -> Result<()> {
+    let test_dir = setup_test_dir("sample-root-node-quoted")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            root_node: r#"data["meta.info"]"#.to_string(),
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    let content = fs::read_to_string(test_dir.join("out/tables/root.csv"))?;
+    assert_eq!(content, "total\n\"3\"\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_root_nodes() -> Result<()> {
+    let root_nodes: Vec<RootNode> = serde_json::from_value(json!([
+        {
+            "root_node": "data.customers",
+            "destination": "customers"
+        },
+        {
+            "root_node": "data.invoices",
+            "destination": "invoices",
+            "mapping": {
+                "number": {
+                    "type": "column",
+                    "mapping": {
+                        "destination": "invoice_number",
+                        "primary_key": true
+                    }
+                },
+                "total": {
+                    "type": "column",
+                    "mapping": {
+                        "destination": "total"
+                    }
+                },
+                "items": {
+                    "type": "table",
+                    "destination": "invoice_items",
+                    "parent_key": {
+                        "destination": "invoice_number"
+                    },
+                    "tableMapping": {
+                        "sku": {
+                            "type": "column",
+                            "mapping": {
+                                "destination": "sku"
+                            }
+                        },
+                        "qty": {
+                            "type": "column",
+                            "mapping": {
+                                "destination": "quantity"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    ]))?;
+
+    for (test_name, streaming) in [
+        ("sample-root-nodes", false),
+        ("sample-root-nodes-streaming", true),
+    ] {
+        let test_dir = setup_test_dir(test_name)?;
+
+        let config = Config {
+            parameters: Parameters {
+                in_type: InputType::Files,
+                root_nodes: root_nodes.clone(),
+                parent_key_field: Some("id".to_string()),
+                streaming,
+                ..Default::default()
+            },
+        };
+        config.validate()?;
+
+        create_config(&test_dir, &config)?;
+
+        let mut parser = Parser::new(config, test_dir.join("out/tables"));
+        parser.process_file(&test_dir.join("in/files/sample.json"))?;
+        parser.write_tables()?;
+
+        // Inferred child tables are prefixed with their root table
+        for table in ["customers", "customers_items", "invoices", "invoice_items"] {
+            compare_csv_files(
+                &test_dir.join(format!("out/tables/{}.csv", table)),
+                &PathBuf::from(format!(
+                    "tests/functional/{}/expected/{}.csv",
+                    test_name, table
+                )),
+            )?;
+        }
+        assert!(!test_dir.join("out/tables/root.csv").exists());
+
+        let manifest: Value = serde_json::from_str(&fs::read_to_string(
+            test_dir.join("out/tables/invoices.csv.manifest"),
+        )?)?;
+        assert_eq!(manifest["primary_key"], json!(["invoice_number"]));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_csv_dialect() -> Result<()> {
+    let test_dir = setup_test_dir("sample-csv-dialect")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            streaming: true,
+            csv: CsvOptions {
+                delimiter: ';',
+                quote_style: QuoteStyle::Necessary,
+                line_terminator: LineTerminator::Crlf,
+                bom: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    };
+    config.validate()?;
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    // The header grew after the first row was written, so the file was rewritten
+    let content = fs::read_to_string(test_dir.join("out/tables/root.csv"))?;
+    assert_eq!(
+        content,
+        "\u{feff}id;a,b;\"say \"\"hi\"\"\";n\r\n1;\"x;y\";;\r\n2;;z;2.5\r\n"
+    );
+
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(
+        test_dir.join("out/tables/root.csv.manifest"),
+    )?)?;
+    assert_eq!(manifest["delimiter"], json!(";"));
+    assert_eq!(manifest["enclosure"], json!("\""));
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_value_format() -> Result<()> {
+    for (test_name, streaming) in [
+        ("sample-value-format", false),
+        ("sample-value-format-streaming", true),
+    ] {
+        let test_dir = setup_test_dir(test_name)?;
+
+        let config = Config {
+            parameters: Parameters {
+                in_type: InputType::Files,
+                root_node: "records".to_string(),
+                streaming,
+                format: FormatOptions {
+                    boolean: BooleanFormat::Numeric,
+                    null: "NULL".to_string(),
+                },
+                ..Default::default()
+            },
+        };
+
+        create_config(&test_dir, &config)?;
+
+        let mut parser = Parser::new(config, test_dir.join("out/tables"));
+        parser.process_file(&test_dir.join("in/files/sample.json"))?;
+        parser.write_tables()?;
+
+        // Numbers keep the digits of the input, exponents get a lowercase `e` and a sign
+        compare_csv_files(
+            &test_dir.join("out/tables/root.csv"),
+            &PathBuf::from(format!("tests/functional/{}/expected/root.csv", test_name)),
+        )?;
+
+        let manifest: Value = serde_json::from_str(&fs::read_to_string(
+            test_dir.join("out/tables/root.csv.manifest"),
+        )?)?;
+        assert_eq!(
+            manifest["column_metadata"]["none"],
+            json!([{"key": "KBC.datatype.basetype", "value": "INTEGER"}])
+        );
+        // Types follow the JSON values, not the `1` and `0` written for booleans
+        assert_eq!(
+            manifest["column_metadata"]["ok"],
+            json!([{"key": "KBC.datatype.basetype", "value": "BOOLEAN"}])
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_json_column() -> Result<()> {
+    let test_dir = setup_test_dir("sample-json-column")?;
+
+    let mapping: IndexMap<String, MappingType> = serde_json::from_value(json!({
+        "id": {
+            "type": "column",
+            "mapping": {
+                "destination": "id"
+            }
+        },
+        "customer": {
+            "type": "json",
+            "mapping": {
+                "destination": "customer"
+            }
+        },
+        "customer.address.city": {
+            "type": "json",
+            "mapping": {
+                "destination": "city"
+            }
+        },
+        "tags": {
+            "type": "json",
+            "mapping": {
+                "destination": "tags"
+            }
+        },
+        "note": {
+            "type": "json",
+            "mapping": {
+                "destination": "note"
+            }
+        }
+    }))?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            mapping,
+            ..Default::default()
+        },
+    };
+    config.validate()?;
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root.csv"),
+        &PathBuf::from("tests/functional/sample-json-column/expected/root.csv"),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_parallel_files() -> Result<()> {
+    let test_dir = setup_test_dir("sample-parallel")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            parent_key_field: Some("id".to_string()),
+            threads: Some(3),
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let paths: Vec<PathBuf> = (1..=5)
+        .map(|i| test_dir.join(format!("in/files/sample{}.json", i)))
+        .collect();
+    let mut results = Vec::new();
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    // The third file is broken and left out, the others keep their order
+    parser.process_files(&paths, 3, |path, result| {
+        results.push((path.to_path_buf(), result.is_ok()));
+        Ok(())
+    })?;
+    parser.write_tables()?;
+
+    let expected: Vec<_> = paths
+        .iter()
+        .cloned()
+        .zip([true, true, false, true, true])
+        .collect();
+    assert_eq!(results, expected);
+    for table in ["root", "root_tags"] {
+        compare_csv_files(
+            &test_dir.join(format!("out/tables/{}.csv", table)),
+            &PathBuf::from(format!(
+                "tests/functional/sample-parallel/expected/{}.csv",
+                table
+            )),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_parallel_output_does_not_depend_on_threads() -> Result<()> {
+    let test_dir = setup_test_dir("sample-parallel-shapes")?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            parent_key_field: Some("id".to_string()),
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    // Only the second file holds `order-item` as an array
+    let paths: Vec<PathBuf> = (1..=3)
+        .map(|i| test_dir.join(format!("in/files/sample{}.json", i)))
+        .collect();
+    for threads in [1, 4] {
+        let out_dir = test_dir.join(format!("out/tables/threads-{}", threads));
+        fs::create_dir_all(&out_dir)?;
+        let mut parser = Parser::new(config.clone(), out_dir.clone());
+        parser.process_files(&paths, threads, |_, result| result)?;
+        parser.write_tables()?;
+
+        for table in ["root", "root_order-item"] {
+            compare_csv_files(
+                &out_dir.join(format!("{}.csv", table)),
+                &PathBuf::from(format!(
+                    "tests/functional/sample-parallel-shapes/expected/{}.csv",
+                    table
+                )),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_compressed_input() -> Result<()> {
+    let test_dir = setup_test_dir("sample-compressed")?;
+    let in_files = test_dir.join("in/files");
+    fs::create_dir_all(&in_files)?;
+
+    let record = |id: u32| json!({ "id": id }).to_string();
+    let gzip = |content: &str| -> Result<Vec<u8>> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content.as_bytes())?;
+        Ok(encoder.finish()?)
+    };
+
+    fs::write(in_files.join("orders.json.gz"), gzip(&record(1))?)?;
+    fs::write(
+        in_files.join("orders.json.zst"),
+        zstd::encode_all(record(2).as_bytes(), 0)?,
+    )?;
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    encoder.write_all(format!("{}\n{}\n", record(3), record(4)).as_bytes())?;
+    fs::write(in_files.join("events.jsonl.bz2"), encoder.finish()?)?;
+    let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+    encoder.write_all(record(5).as_bytes())?;
+    fs::write(in_files.join("orders.json.xz"), encoder.finish()?)?;
+    // Recognized by its magic number despite the plain extension
+    fs::write(in_files.join("disguised.json"), gzip(&record(6))?)?;
+
+    let mut zip = zip::ZipWriter::new(File::create(in_files.join("bundle.zip"))?);
+    let options = zip::write::SimpleFileOptions::default();
+    zip.add_directory("2024/", options)?;
+    zip.start_file("2024/a.json", options)?;
+    zip.write_all(record(7).as_bytes())?;
+    zip.start_file("b.json.gz", options)?;
+    zip.write_all(&gzip(&record(8))?)?;
+    zip.start_file("README.txt", options)?;
+    zip.write_all(b"not converted")?;
+    zip.finish()?;
+
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+        File::create(in_files.join("bundle.tar.gz"))?,
+        flate2::Compression::default(),
+    ));
+    let content = record(9);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tar.append_data(&mut header, "c.json", content.as_bytes())?;
+    tar.into_inner()?.finish()?;
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            add_file_name: true,
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    for file in [
+        "orders.json.gz",
+        "orders.json.zst",
+        "events.jsonl.bz2",
+        "orders.json.xz",
+        "disguised.json",
+        "bundle.zip",
+        "bundle.tar.gz",
+    ] {
+        let path = in_files.join(file);
+        assert!(json2csv_processor::input::is_input(&path), "{}", file);
+        parser.process_file(&path)?;
+    }
+    parser.write_tables()?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root.csv"),
+        &PathBuf::from("tests/functional/sample-compressed/expected/root.csv"),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_csv_input() -> Result<()> {
+    let test_dir = setup_test_dir("sample-csv-input")?;
+
+    // Rows with an empty JSON column produce no records
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Tables,
+            parent_key_field: Some("name".to_string()),
+            csv_input: Some(CsvInput {
+                json_column: "payload".to_string(),
+                carry_columns: vec!["id".to_string()],
+            }),
+            ..Default::default()
+        },
+    };
+    config.validate()?;
+
+    create_config(&test_dir, &config)?;
+
+    let in_tables = test_dir.join("in/tables");
+    let input_files = json2csv_processor::input::list_files(&in_tables, &config.parameters)?;
+    assert_eq!(input_files, [in_tables.join("orders.csv")]);
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&in_tables.join("orders.csv"))?;
+    parser.write_tables()?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root.csv"),
+        &PathBuf::from("tests/functional/sample-csv-input/expected/root.csv"),
+    )?;
+
+    compare_csv_files(
+        &test_dir.join("out/tables/root_items.csv"),
+        &PathBuf::from("tests/functional/sample-csv-input/expected/root_items.csv"),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_gzip_output() -> Result<()> {
+    let test_dir = setup_test_dir("sample-gzip-output")?;
+
+    // Streaming rewrites the file for the late column before compressing it
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            streaming: true,
+            compression: CompressionOptions {
+                format: CompressionFormat::Gzip,
+                level: 9,
+            },
+            ..Default::default()
+        },
+    };
+    config.validate()?;
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    let out_tables = test_dir.join("out/tables");
+    assert!(!out_tables.join("root.csv").exists());
+    assert!(!out_tables.join("root.csv.manifest").exists());
+    assert!(out_tables.join("root.csv.gz.manifest").exists());
+
+    let mut content = String::new();
+    flate2::read::GzDecoder::new(File::open(out_tables.join("root.csv.gz"))?)
+        .read_to_string(&mut content)?;
+    assert_eq!(
+        content,
+        fs::read_to_string("tests/functional/sample-gzip-output/expected/root.csv")?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_sliced_output() -> Result<()> {
+    let test_dir = setup_test_dir("sample-sliced")?;
+    // The table directory of a previous run would be in the way of the CSV file
+    let table_dir = test_dir.join("out/tables/root.csv");
+    if table_dir.is_dir() {
+        fs::remove_dir_all(&table_dir)?;
+    }
+
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            slicing: SlicingOptions {
+                max_rows: Some(2),
+                max_bytes: None,
+            },
+            ..Default::default()
+        },
+    };
+    config.validate()?;
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    // Slices have no header, the manifest lists the columns
+    assert!(table_dir.is_dir());
+    let slices: Vec<String> = ["part0001.csv", "part0002.csv", "part0003.csv"]
+        .iter()
+        .map(|slice| fs::read_to_string(table_dir.join(slice)))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(
+        slices,
+        [
+            "\"1\",\"A\",\"\"\n\"2\",\"B\",\"\"\n",
+            "\"3\",\"C\",\"\"\n\"4\",\"D\",\"late\"\n",
+            "\"5\",\"E\",\"\"\n",
+        ]
+    );
+    assert!(!table_dir.join("part0004.csv").exists());
+
+    let manifest: Value = serde_json::from_str(&fs::read_to_string(
+        test_dir.join("out/tables/root.csv.manifest"),
+    )?)?;
+    assert_eq!(manifest["columns"], json!(["id", "name", "note"]));
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_with_gzip_slices_by_size() -> Result<()> {
+    let test_dir = setup_test_dir("sample-sliced-gzip")?;
+    let table_dir = test_dir.join("out/tables/root.csv.gz");
+    if table_dir.is_dir() {
+        fs::remove_dir_all(&table_dir)?;
+    }
+    fs::create_dir_all(test_dir.join("in/files"))?;
+    fs::copy(
+        "tests/functional/sample-sliced/source/sample.json",
+        test_dir.join("in/files/sample.json"),
+    )?;
+
+    // Every row takes 11 bytes, so a slice is complete after its second row
+    let config = Config {
+        parameters: Parameters {
+            in_type: InputType::Files,
+            streaming: true,
+            compression: CompressionOptions {
+                format: CompressionFormat::Gzip,
+                level: 6,
+            },
+            slicing: SlicingOptions {
+                max_rows: None,
+                max_bytes: Some(20),
+            },
+            ..Default::default()
+        },
+    };
+
+    create_config(&test_dir, &config)?;
+
+    let mut parser = Parser::new(config, test_dir.join("out/tables"));
+    parser.process_file(&test_dir.join("in/files/sample.json"))?;
+    parser.write_tables()?;
+
+    let mut rows = Vec::new();
+    for slice in ["part0001.csv.gz", "part0002.csv.gz", "part0003.csv.gz"] {
+        let mut content = String::new();
+        flate2::read::GzDecoder::new(File::open(table_dir.join(slice))?)
+            .read_to_string(&mut content)?;
+        rows.push(content.lines().count());
+    }
+    assert_eq!(rows, [2, 2, 1]);
+    assert!(test_dir.join("out/tables/root.csv.gz.manifest").exists());
+    assert!(!test_dir.join("out/tables/root.csv").exists());
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use json2csv_processor::config::{CsvInput, FileOrder, InputFiles, Parameters};
+use json2csv_processor::input::list_files;
+use std::fs::{self, File};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+fn input_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    for file in [
+        "orders_2.json",
+        "orders_1.json.gz",
+        "customers.json",
+        "notes.txt",
+        "orders.csv",
+        "2024/orders_3.json",
+        "2024/archive/orders_4.json",
+    ] {
+        let path = dir.path().join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "{}").unwrap();
+    }
+    dir
+}
+
+fn relative_names(dir: &Path, input_files: &InputFiles) -> Vec<String> {
+    let params = Parameters {
+        input_files: input_files.clone(),
+        ..Default::default()
+    };
+    relative_names_with(dir, &params)
+}
+
+fn relative_names_with(dir: &Path, params: &Parameters) -> Vec<String> {
+    list_files(dir, params)
+        .unwrap()
+        .iter()
+        .map(|path| {
+            path.strip_prefix(dir)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn test_include_and_exclude() {
+    let dir = input_dir();
+    let mut input_files = InputFiles {
+        order: FileOrder::Name,
+        ..Default::default()
+    };
+    assert_eq!(
+        relative_names(dir.path(), &input_files),
+        [
+            "2024/archive/orders_4.json",
+            "2024/orders_3.json",
+            "customers.json",
+            "orders_1.json.gz",
+            "orders_2.json",
+        ]
+    );
+
+    // Patterns without a folder match the file name at any depth
+    input_files.include = vec!["orders_*".to_string()];
+    input_files.exclude = vec!["2024/archive/*".to_string()];
+    assert_eq!(
+        relative_names(dir.path(), &input_files),
+        ["2024/orders_3.json", "orders_1.json.gz", "orders_2.json"]
+    );
+
+    input_files.max_depth = Some(1);
+    assert_eq!(
+        relative_names(dir.path(), &input_files),
+        ["orders_1.json.gz", "orders_2.json"]
+    );
+}
+
+#[test]
+fn test_order_by_modification_time() {
+    let dir = input_dir();
+    let now = SystemTime::now();
+    for (file, age) in [("orders_2.json", 20), ("customers.json", 10)] {
+        File::options()
+            .write(true)
+            .open(dir.path().join(file))
+            .unwrap()
+            .set_modified(now - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    let input_files = InputFiles {
+        include: vec!["*.json".to_string()],
+        max_depth: Some(1),
+        order: FileOrder::Mtime,
+        ..Default::default()
+    };
+    assert_eq!(
+        relative_names(dir.path(), &input_files),
+        ["orders_2.json", "customers.json"]
+    );
+}
+
+#[test]
+fn test_csv_tables_with_csv_input() {
+    let dir = input_dir();
+    let mut params = Parameters {
+        input_files: InputFiles {
+            max_depth: Some(1),
+            order: FileOrder::Name,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(
+        relative_names_with(dir.path(), &params),
+        ["customers.json", "orders_1.json.gz", "orders_2.json"]
+    );
+
+    params.csv_input = Some(CsvInput {
+        json_column: "payload".to_string(),
+        carry_columns: Vec::new(),
+    });
+    assert_eq!(
+        relative_names_with(dir.path(), &params),
+        [
+            "customers.json",
+            "orders.csv",
+            "orders_1.json.gz",
+            "orders_2.json"
+        ]
+    );
+}
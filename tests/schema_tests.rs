@@ -0,0 +1,115 @@
+use json2csv_processor::schema::{ColumnType, SchemaChange, State};
+use serde_json::json;
+
+#[test]
+fn test_column_type_of_value() {
+    assert_eq!(ColumnType::of_value(&json!(null)), None);
+    assert_eq!(ColumnType::of_value(&json!("")), None);
+    assert_eq!(
+        ColumnType::of_value(&json!(false)),
+        Some(ColumnType::Boolean)
+    );
+    assert_eq!(ColumnType::of_value(&json!(100)), Some(ColumnType::Integer));
+    assert_eq!(ColumnType::of_value(&json!(1.5)), Some(ColumnType::Numeric));
+    // Strings are never numbers or booleans
+    assert_eq!(
+        ColumnType::of_value(&json!("100")),
+        Some(ColumnType::String)
+    );
+    assert_eq!(
+        ColumnType::of_value(&json!("true")),
+        Some(ColumnType::String)
+    );
+    assert_eq!(
+        ColumnType::of_value(&json!("2024-02-29")),
+        Some(ColumnType::Date)
+    );
+    assert_eq!(
+        ColumnType::of_value(&json!("2024-02-29T12:30:00Z")),
+        Some(ColumnType::Timestamp)
+    );
+    assert_eq!(
+        ColumnType::of_value(&json!({"a": 1})),
+        Some(ColumnType::String)
+    );
+}
+
+#[test]
+fn test_infer_column_type() {
+    assert_eq!(ColumnType::infer(""), None);
+    assert_eq!(ColumnType::infer("true"), Some(ColumnType::Boolean));
+    assert_eq!(ColumnType::infer("-42"), Some(ColumnType::Integer));
+    assert_eq!(ColumnType::infer("0"), Some(ColumnType::Integer));
+    assert_eq!(ColumnType::infer("2.5e-3"), Some(ColumnType::Numeric));
+    assert_eq!(ColumnType::infer("007"), Some(ColumnType::String));
+    assert_eq!(ColumnType::infer("1."), Some(ColumnType::String));
+    assert_eq!(ColumnType::infer("NaN"), Some(ColumnType::String));
+    assert_eq!(ColumnType::infer("2024-02-29"), Some(ColumnType::Date));
+    assert_eq!(ColumnType::infer("2024-13-01"), Some(ColumnType::String));
+    assert_eq!(
+        ColumnType::infer("2024-02-29T12:30:00Z"),
+        Some(ColumnType::Timestamp)
+    );
+    assert_eq!(
+        ColumnType::infer("2024-02-29 12:30:00.123+01:00"),
+        Some(ColumnType::Timestamp)
+    );
+    assert_eq!(
+        ColumnType::infer("2024-02-29T12:30"),
+        Some(ColumnType::String)
+    );
+    assert_eq!(ColumnType::infer("2024-ěš-01"), Some(ColumnType::String));
+
+    assert_eq!(
+        ColumnType::Integer.merge(ColumnType::Numeric),
+        ColumnType::Numeric
+    );
+    assert_eq!(
+        ColumnType::Boolean.merge(ColumnType::Integer),
+        ColumnType::Mixed
+    );
+    assert_eq!(
+        ColumnType::Date.merge(ColumnType::Timestamp),
+        ColumnType::Timestamp
+    );
+    assert_eq!(
+        ColumnType::String.merge(ColumnType::Date),
+        ColumnType::Mixed
+    );
+}
+
+#[test]
+fn test_changes_since() {
+    let previous: State = serde_json::from_value(json!({"tables": {
+        "root": {"columns": {"id": "integer", "price": "integer", "note": null, "old": "string"}},
+        "gone": {"columns": {"id": "integer"}}
+    }}))
+    .unwrap();
+    let current: State = serde_json::from_value(json!({"tables": {
+        "root": {"columns": {"id": "integer", "price": "numeric", "note": "string", "new": null}},
+        "items": {"columns": {"sku": "string"}}
+    }}))
+    .unwrap();
+
+    assert_eq!(
+        current.changes_since(&previous),
+        vec![
+            SchemaChange::TableAdded("items".to_string()),
+            SchemaChange::TypeChanged {
+                table: "root".to_string(),
+                column: "price".to_string(),
+                from: ColumnType::Integer,
+                to: ColumnType::Numeric,
+            },
+            SchemaChange::ColumnAdded {
+                table: "root".to_string(),
+                column: "new".to_string(),
+            },
+            SchemaChange::ColumnRemoved {
+                table: "root".to_string(),
+                column: "old".to_string(),
+            },
+            SchemaChange::TableRemoved("gone".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,77 @@
+use json2csv_processor::selector::{Segment, Selector, SelectorError};
+use serde_json::json;
+
+fn segments(selector: &str) -> Vec<Segment> {
+    selector.parse::<Selector>().unwrap().segments().to_vec()
+}
+
+#[test]
+fn test_parse_selectors() {
+    assert_eq!(segments(""), vec![]);
+    assert_eq!(
+        segments("data.records"),
+        vec![
+            Segment::Key("data".to_string()),
+            Segment::Key("records".to_string())
+        ]
+    );
+    assert_eq!(
+        segments("$.pages[*].records[0]"),
+        vec![
+            Segment::Key("pages".to_string()),
+            Segment::Wildcard,
+            Segment::Key("records".to_string()),
+            Segment::Index(0)
+        ]
+    );
+    assert_eq!(
+        segments(r#"data["user.name"].*"#),
+        vec![
+            Segment::Key("data".to_string()),
+            Segment::Key("user.name".to_string()),
+            Segment::Wildcard
+        ]
+    );
+    assert_eq!(
+        segments(r"user\.name.\*"),
+        vec![
+            Segment::Key("user.name".to_string()),
+            Segment::Key("*".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |selector: &str| selector.parse::<Selector>().unwrap_err();
+
+    assert_eq!(error("data..items"), SelectorError::EmptyKey(5));
+    assert_eq!(error("data."), SelectorError::EmptyKey(5));
+    assert_eq!(error("data[0"), SelectorError::UnterminatedBracket(4));
+    assert_eq!(error("data['x]"), SelectorError::UnterminatedQuote(5));
+    assert_eq!(
+        error("data[x]"),
+        SelectorError::InvalidIndex("x".to_string())
+    );
+    assert_eq!(error("data[0]x"), SelectorError::UnexpectedChar(7, 'x'));
+}
+
+#[test]
+fn test_select_fans_out_over_wildcards() {
+    let value = json!({
+        "pages": [
+            {"records": [1, 2]},
+            {"other": true},
+            {"records": [3]}
+        ]
+    });
+
+    let selector: Selector = "pages[*].records".parse().unwrap();
+    assert_eq!(selector.select(&value), vec![&json!([1, 2]), &json!([3])]);
+
+    let selector: Selector = "pages[2].records[0]".parse().unwrap();
+    assert_eq!(selector.select(&value), vec![&json!(3)]);
+
+    let selector: Selector = "pages[5]".parse().unwrap();
+    assert!(selector.select(&value).is_empty());
+}